@@ -123,7 +123,8 @@ impl IndexedBlock {
     }
 }
 
-fn merkle_root(transactions: &[IndexedTransaction]) -> H256 {
+/// Merkle root hash of transactions, the `merkle_root_hash` of the block header.
+pub fn merkle_root(transactions: &[IndexedTransaction]) -> H256 {
     let hashes = transactions
         .iter()
         .map(|txn| get_transaction_hash_for_merkle_tree(&txn.raw))
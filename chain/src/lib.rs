@@ -1,7 +1,7 @@
 pub use primitive_types::H256;
 pub use proto2::chain::{Block, BlockHeader, Transaction};
 
-pub use indexed_block::IndexedBlock;
+pub use indexed_block::{merkle_root, IndexedBlock};
 pub use indexed_header::IndexedBlockHeader;
 pub use indexed_transaction::IndexedTransaction;
 
@@ -38,6 +38,21 @@ impl Address {
         Address::from_public(&Public::from_private(private).expect("public from private; qed"))
     }
 
+    /// Address of a smart contract created by a `CreateSmartContract` transaction.
+    ///
+    /// Renamed: `generateContractAddress`
+    pub fn from_contract_creation(txn_hash: &[u8], owner: &Address) -> Address {
+        let mut hasher = Keccak256::new();
+        hasher.update(txn_hash);
+        hasher.update(owner);
+        let digest = hasher.finalize();
+
+        let mut raw = [ADDRESS_TYPE_PREFIX; 21];
+        raw[1..21].copy_from_slice(&digest[digest.len() - 20..]);
+
+        Address(raw)
+    }
+
     /// As raw 21-byte address.
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
@@ -1,8 +1,10 @@
 mod merkle_tree;
+mod sparse_merkle_tree;
 mod tree;
 use primitive_types::H256;
 
 pub use crate::merkle_tree::MerkleTree;
+pub use crate::sparse_merkle_tree::{SparseMerkleProof, SparseMerkleTree};
 
 /// A hashable type
 pub trait MerkleHasher {
@@ -17,6 +19,12 @@ pub trait MerkleHasher {
     }
 }
 
+/// A hasher separating leaf and inner node hashes, required by the sparse Merkle tree.
+pub trait TaggedMerkleHasher: MerkleHasher {
+    /// Hash of a tag byte followed by two nodes.
+    fn hash_tagged_nodes(tag: u8, left: &H256, right: &H256) -> H256;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,6 +48,17 @@ mod tests {
         }
     }
 
+    impl TaggedMerkleHasher for BytesSha256Hasher {
+        fn hash_tagged_nodes(tag: u8, left: &H256, right: &H256) -> H256 {
+            let result = Sha256::new()
+                .chain([tag])
+                .chain(left.as_bytes())
+                .chain(right.as_bytes())
+                .finalize();
+            unsafe { mem::transmute(result) }
+        }
+    }
+
     #[test]
     fn empty_tree() {
         let list: Vec<Vec<u8>> = vec![];
@@ -0,0 +1,403 @@
+use primitive_types::H256;
+use std::marker::PhantomData;
+use std::mem;
+
+use crate::TaggedMerkleHasher;
+
+/// Domain tags of hash preimages, so that a leaf can never be taken as an inner node, or vice versa.
+const LEAF_TAG: u8 = 0x00;
+const NODE_TAG: u8 = 0x01;
+
+/// Bit of the key at depth, from the most significant bit.
+fn bit_at(key: &H256, depth: usize) -> u8 {
+    key.as_bytes()[depth / 8] >> (7 - depth % 8) & 1
+}
+
+fn leaf_hash<H: TaggedMerkleHasher>(key: &H256, value_hash: &H256) -> H256 {
+    H::hash_tagged_nodes(LEAF_TAG, key, value_hash)
+}
+
+fn node_hash<H: TaggedMerkleHasher>(left: &H256, right: &H256) -> H256 {
+    H::hash_tagged_nodes(NODE_TAG, left, right)
+}
+
+/// A subtree, with hashes of leaves and inner nodes cached.
+#[derive(Clone, Debug)]
+enum Node {
+    Empty,
+    Leaf {
+        key: H256,
+        value_hash: H256,
+        hash: H256,
+    },
+    /// Always has at least 2 leaves, a subtree of a single leaf is the leaf itself.
+    Internal {
+        left: Box<Node>,
+        right: Box<Node>,
+        hash: H256,
+    },
+}
+
+impl Node {
+    fn leaf<H: TaggedMerkleHasher>(key: H256, value_hash: H256) -> Node {
+        let hash = leaf_hash::<H>(&key, &value_hash);
+        Node::Leaf { key, value_hash, hash }
+    }
+
+    fn internal<H: TaggedMerkleHasher>(left: Node, right: Node) -> Node {
+        let hash = node_hash::<H>(&left.hash::<H>(), &right.hash::<H>());
+        Node::Internal {
+            left: Box::new(left),
+            right: Box::new(right),
+            hash,
+        }
+    }
+
+    fn hash<H: TaggedMerkleHasher>(&self) -> H256 {
+        match *self {
+            Node::Empty => H::hash_empty(),
+            Node::Leaf { hash, .. } | Node::Internal { hash, .. } => hash,
+        }
+    }
+}
+
+/// Subtree at depth holding both leaves, which are known to have different keys.
+fn split<H: TaggedMerkleHasher>(a: Node, a_key: &H256, b: Node, b_key: &H256, depth: usize) -> Node {
+    match (bit_at(a_key, depth), bit_at(b_key, depth)) {
+        (0, 0) => Node::internal::<H>(split::<H>(a, a_key, b, b_key, depth + 1), Node::Empty),
+        (1, 1) => Node::internal::<H>(Node::Empty, split::<H>(a, a_key, b, b_key, depth + 1)),
+        (0, _) => Node::internal::<H>(a, b),
+        _ => Node::internal::<H>(b, a),
+    }
+}
+
+/// Insert or update a leaf in the subtree at depth, rehashing the path. Returns true if the key is new.
+fn insert_at<H: TaggedMerkleHasher>(node: &mut Node, key: H256, value_hash: H256, depth: usize) -> bool {
+    match node {
+        Node::Empty => {
+            *node = Node::leaf::<H>(key, value_hash);
+            true
+        }
+        Node::Leaf { key: leaf_key, .. } if *leaf_key == key => {
+            *node = Node::leaf::<H>(key, value_hash);
+            false
+        }
+        Node::Leaf { key: leaf_key, .. } => {
+            let leaf_key = *leaf_key;
+            let leaf = mem::replace(node, Node::Empty);
+            *node = split::<H>(leaf, &leaf_key, Node::leaf::<H>(key, value_hash), &key, depth);
+            true
+        }
+        Node::Internal { left, right, hash } => {
+            let inserted = if bit_at(&key, depth) == 0 {
+                insert_at::<H>(left, key, value_hash, depth + 1)
+            } else {
+                insert_at::<H>(right, key, value_hash, depth + 1)
+            };
+            *hash = node_hash::<H>(&left.hash::<H>(), &right.hash::<H>());
+            inserted
+        }
+    }
+}
+
+/// Remove a leaf from the subtree at depth, rehashing the path. Returns true if the key existed.
+fn remove_at<H: TaggedMerkleHasher>(node: &mut Node, key: &H256, depth: usize) -> bool {
+    match node {
+        Node::Leaf { key: leaf_key, .. } if leaf_key == key => {
+            *node = Node::Empty;
+            true
+        }
+        Node::Internal { left, right, hash } => {
+            let removed = if bit_at(key, depth) == 0 {
+                remove_at::<H>(left, key, depth + 1)
+            } else {
+                remove_at::<H>(right, key, depth + 1)
+            };
+            if !removed {
+                return false;
+            }
+            // a single leaf left, replaces the subtree
+            let remaining = match (&**left, &**right) {
+                (Node::Leaf { .. }, Node::Empty) => Some(mem::replace(&mut **left, Node::Empty)),
+                (Node::Empty, Node::Leaf { .. }) => Some(mem::replace(&mut **right, Node::Empty)),
+                _ => {
+                    *hash = node_hash::<H>(&left.hash::<H>(), &right.hash::<H>());
+                    None
+                }
+            };
+            if let Some(leaf) = remaining {
+                *node = leaf;
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// A sparse Merkle tree of 256-bit keys.
+///
+/// Subtrees of a single leaf are replaced by the leaf, and empty subtrees hash to `hash_empty()`, so the tree
+/// is as high as needed to separate keys, instead of 256 levels. A leaf hashes as
+/// `hash_tagged_nodes(0x00, key, hash(value))`, an inner node as `hash_tagged_nodes(0x01, left, right)`.
+///
+/// Node hashes are kept in the tree, an update rehashes only the path from the leaf to the root.
+#[derive(Clone, Debug)]
+pub struct SparseMerkleTree<H: TaggedMerkleHasher> {
+    root: Node,
+    len: usize,
+    _hasher: PhantomData<H>,
+}
+
+impl<H: TaggedMerkleHasher> Default for SparseMerkleTree<H> {
+    fn default() -> Self {
+        SparseMerkleTree {
+            root: Node::Empty,
+            len: 0,
+            _hasher: PhantomData,
+        }
+    }
+}
+
+impl<H: TaggedMerkleHasher> SparseMerkleTree<H> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Insert or update a value.
+    pub fn insert(&mut self, key: H256, value: &H::Input) {
+        if insert_at::<H>(&mut self.root, key, H::hash(value), 0) {
+            self.len += 1;
+        }
+    }
+
+    pub fn remove(&mut self, key: &H256) -> bool {
+        let removed = remove_at::<H>(&mut self.root, key, 0);
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn contains_key(&self, key: &H256) -> bool {
+        match self.find_leaf(key).0 {
+            Node::Leaf { key: leaf_key, .. } => leaf_key == key,
+            _ => false,
+        }
+    }
+
+    /// Returns the number of leaves
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the root hash.
+    pub fn root_hash(&self) -> H256 {
+        self.root.hash::<H>()
+    }
+
+    /// Walk down the path of the key, returns the leaf or empty subtree where it ends, and the siblings.
+    fn find_leaf(&self, key: &H256) -> (&Node, Vec<H256>) {
+        let mut node = &self.root;
+        let mut siblings = vec![];
+        let mut depth = 0;
+        while let Node::Internal { left, right, .. } = node {
+            if bit_at(key, depth) == 0 {
+                siblings.push(right.hash::<H>());
+                node = left;
+            } else {
+                siblings.push(left.hash::<H>());
+                node = right;
+            }
+            depth += 1;
+        }
+        (node, siblings)
+    }
+
+    /// Proof of the key's inclusion, or exclusion if the key does not exist.
+    pub fn prove(&self, key: &H256) -> SparseMerkleProof {
+        let (node, siblings) = self.find_leaf(key);
+        let leaf = match *node {
+            Node::Leaf { key, value_hash, .. } => Some((key, value_hash)),
+            _ => None,
+        };
+        SparseMerkleProof { leaf, siblings }
+    }
+}
+
+/// Merkle proof of a key in a `SparseMerkleTree`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseMerkleProof {
+    /// The leaf (key, hash of value) where the search of the key ends, `None` for an empty subtree.
+    pub leaf: Option<(H256, H256)>,
+    /// Sibling hashes from the root down to the leaf.
+    pub siblings: Vec<H256>,
+}
+
+impl SparseMerkleProof {
+    /// Verify the key has the value under root, or does not exist if `value` is `None`.
+    pub fn verify<H: TaggedMerkleHasher>(&self, root: &H256, key: &H256, value: Option<&H::Input>) -> bool {
+        if self.siblings.len() > 256 {
+            return false;
+        }
+        let mut current = match (value, &self.leaf) {
+            (Some(value), Some((leaf_key, value_hash))) => {
+                if leaf_key != key || *value_hash != H::hash(value) {
+                    return false;
+                }
+                leaf_hash::<H>(leaf_key, value_hash)
+            }
+            (Some(_), None) => return false,
+            // another key is in the subtree where the key would be
+            (None, Some((leaf_key, value_hash))) => {
+                if leaf_key == key ||
+                    (0..self.siblings.len()).any(|depth| bit_at(leaf_key, depth) != bit_at(key, depth))
+                {
+                    return false;
+                }
+                leaf_hash::<H>(leaf_key, value_hash)
+            }
+            (None, None) => H::hash_empty(),
+        };
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            current = if bit_at(key, depth) == 0 {
+                node_hash::<H>(&current, sibling)
+            } else {
+                node_hash::<H>(sibling, &current)
+            };
+        }
+        current == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::BytesSha256Hasher;
+    use crate::MerkleHasher;
+
+    type Tree = SparseMerkleTree<BytesSha256Hasher>;
+
+    fn key(prefix: &[u8]) -> H256 {
+        let mut key = H256::zero();
+        key[..prefix.len()].copy_from_slice(prefix);
+        key
+    }
+
+    #[test]
+    fn sparse_tree_root() {
+        let mut tree = Tree::new();
+        assert_eq!(tree.root_hash(), H256::zero());
+
+        tree.insert(key(&[0x00]), &b"a".to_vec());
+        let single_root = tree.root_hash();
+        assert_eq!(
+            single_root,
+            leaf_hash::<BytesSha256Hasher>(&key(&[0x00]), &BytesSha256Hasher::hash(&b"a".to_vec()))
+        );
+
+        tree.insert(key(&[0x80]), &b"b".to_vec());
+        tree.insert(key(&[0x40]), &b"c".to_vec());
+        let root = tree.root_hash();
+
+        // independent of insertion order
+        let mut other = Tree::new();
+        other.insert(key(&[0x40]), &b"c".to_vec());
+        other.insert(key(&[0x80]), &b"b".to_vec());
+        other.insert(key(&[0x00]), &b"a".to_vec());
+        assert_eq!(other.root_hash(), root);
+
+        other.insert(key(&[0x40]), &b"changed".to_vec());
+        assert_ne!(other.root_hash(), root);
+
+        assert!(tree.remove(&key(&[0x80])));
+        assert!(tree.remove(&key(&[0x40])));
+        assert!(!tree.remove(&key(&[0x40])));
+        assert_eq!(tree.root_hash(), single_root);
+    }
+
+    #[test]
+    fn sparse_tree_proof() {
+        let mut tree = Tree::new();
+        let keys: Vec<_> = (0..16u8).map(|i| key(&[i.wrapping_mul(37), i])).collect();
+        for (i, k) in keys.iter().enumerate() {
+            tree.insert(*k, &vec![i as u8]);
+        }
+        let root = tree.root_hash();
+
+        for (i, k) in keys.iter().enumerate() {
+            let proof = tree.prove(k);
+            assert!(proof.verify::<BytesSha256Hasher>(&root, k, Some(&vec![i as u8])));
+            assert!(!proof.verify::<BytesSha256Hasher>(&root, k, Some(&vec![0xff])));
+            assert!(!proof.verify::<BytesSha256Hasher>(&root, k, None));
+        }
+
+        // exclusion, ends at either a different leaf or an empty subtree
+        for absent in &[key(&[0x01]), key(&[0xff, 0xff]), key(&[keys[3][0], keys[3][1], 0x01])] {
+            let proof = tree.prove(absent);
+            assert!(proof.verify::<BytesSha256Hasher>(&root, absent, None));
+            assert!(!proof.verify::<BytesSha256Hasher>(&root, absent, Some(&vec![3])));
+        }
+
+        assert!(Tree::new()
+            .prove(&keys[0])
+            .verify::<BytesSha256Hasher>(&H256::zero(), &keys[0], None));
+    }
+
+    #[test]
+    fn sparse_tree_inner_node_is_not_leaf() {
+        let mut tree = Tree::new();
+        let (a, b) = (key(&[0x00]), key(&[0x80]));
+        tree.insert(a, &b"a".to_vec());
+        tree.insert(b, &b"b".to_vec());
+        let root = tree.root_hash();
+
+        // the root node taken as a leaf of its children hashes, to prove the exclusion of an existing key
+        let children = tree.prove(&a).siblings[0];
+        let forged = SparseMerkleProof {
+            leaf: Some((leaf_hash::<BytesSha256Hasher>(&a, &BytesSha256Hasher::hash(&b"a".to_vec())), children)),
+            siblings: vec![],
+        };
+        assert!(!forged.verify::<BytesSha256Hasher>(&root, &a, None));
+    }
+
+    #[test]
+    fn sparse_tree_incremental_update() {
+        // keys sharing long prefixes
+        let keys: Vec<_> = (0..64u8).map(|i| key(&[i & 0xf0, i.wrapping_mul(101), i])).collect();
+        let mut tree = Tree::new();
+        for (i, k) in keys.iter().enumerate() {
+            tree.insert(*k, &vec![i as u8]);
+        }
+        for k in keys.iter().step_by(3) {
+            assert!(tree.remove(k));
+        }
+        for k in keys.iter().step_by(5) {
+            tree.insert(*k, &b"updated".to_vec());
+        }
+
+        // same leaves, inserted in reverse order
+        let mut expected = Tree::new();
+        for (i, k) in keys.iter().enumerate().rev() {
+            if i % 5 == 0 {
+                expected.insert(*k, &b"updated".to_vec());
+            } else if i % 3 != 0 {
+                expected.insert(*k, &vec![i as u8]);
+            }
+        }
+        assert_eq!(tree.len(), expected.len());
+        assert_eq!(tree.root_hash(), expected.root_hash());
+        for (i, k) in keys.iter().enumerate() {
+            assert_eq!(tree.contains_key(k), i % 5 == 0 || i % 3 != 0);
+        }
+
+        for k in &keys {
+            tree.remove(k);
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.root_hash(), H256::zero());
+    }
+}
@@ -0,0 +1,9 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_client(false)
+        .extern_path(".proto.common", "::proto2::common")
+        .extern_path(".proto.chain", "::proto2::chain")
+        .compile(&["proto/wallet.proto"], &["proto/", "../proto2/proto/"])
+        .unwrap();
+}
@@ -0,0 +1,440 @@
+//! Contract ABI decoding, for call data.
+
+use crypto::keccak256;
+use keys::Address;
+use primitive_types::U256;
+use lazy_static::lazy_static;
+use proto2::common::smart_contract::abi::{Entry, EntryType, Param};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// Unsupported or malformed ABI type string.
+    InvalidType(String),
+    /// Data is shorter than its declared layout.
+    InvalidData,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidType(ref ty) => write!(f, "invalid abi type: {}", ty),
+            Error::InvalidData => write!(f, "invalid abi data"),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+/// Max length of a fixed-size array type, like `T[N]`.
+const MAX_FIXED_ARRAY_LEN: usize = 1024;
+/// Max nesting depth of array types, like `T[][]`.
+const MAX_ARRAY_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamType {
+    Address,
+    Bool,
+    Int(usize),
+    Uint(usize),
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+}
+
+impl ParamType {
+    pub fn parse(ty: &str) -> Result<ParamType, Error> {
+        ParamType::parse_with_depth(ty, 0)
+    }
+
+    fn parse_with_depth(ty: &str, depth: usize) -> Result<ParamType, Error> {
+        let invalid = || Error::InvalidType(ty.to_owned());
+
+        if ty.ends_with(']') {
+            if depth >= MAX_ARRAY_DEPTH {
+                return Err(invalid());
+            }
+            let pos = ty.rfind('[').ok_or_else(invalid)?;
+            let inner = Box::new(ParamType::parse_with_depth(&ty[..pos], depth + 1)?);
+            let len = &ty[pos + 1..ty.len() - 1];
+            if len.is_empty() {
+                return Ok(ParamType::Array(inner));
+            }
+            let ty = match len.parse() {
+                Ok(len) if len > 0 && len <= MAX_FIXED_ARRAY_LEN => ParamType::FixedArray(inner, len),
+                _ => return Err(invalid()),
+            };
+            // the whole head part must be addressable
+            ty.head_len().ok_or_else(invalid)?;
+            return Ok(ty);
+        }
+
+        match ty {
+            "address" => Ok(ParamType::Address),
+            "bool" => Ok(ParamType::Bool),
+            "bytes" => Ok(ParamType::Bytes),
+            "string" => Ok(ParamType::String),
+            "int" => Ok(ParamType::Int(256)),
+            "uint" => Ok(ParamType::Uint(256)),
+            // TRC10 token id, an alias of uint256
+            "trcToken" => Ok(ParamType::Uint(256)),
+            _ if ty.starts_with("uint") => ty[4..].parse().map(ParamType::Uint).map_err(|_| invalid()),
+            _ if ty.starts_with("int") => ty[3..].parse().map(ParamType::Int).map_err(|_| invalid()),
+            _ if ty.starts_with("bytes") => ty[5..].parse().map(ParamType::FixedBytes).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match *self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(ref inner, _) => inner.is_dynamic(),
+            _ => false,
+        }
+    }
+
+    /// Size in the head part of an encoded tuple, `None` on overflow.
+    fn head_len(&self) -> Option<usize> {
+        match *self {
+            _ if self.is_dynamic() => Some(32),
+            ParamType::FixedArray(ref inner, len) => inner.head_len()?.checked_mul(len),
+            _ => Some(32),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Address(Address),
+    Bool(bool),
+    /// Two's complement signed integer.
+    Int(U256),
+    Uint(U256),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<Token>),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Address(ref addr) => write!(f, "{}", addr),
+            Token::Bool(val) => write!(f, "{}", val),
+            Token::Int(val) if val.bit(255) => write!(f, "-{}", (!val).overflowing_add(U256::one()).0),
+            Token::Int(val) | Token::Uint(val) => write!(f, "{}", val),
+            Token::FixedBytes(ref raw) | Token::Bytes(ref raw) => write!(f, "{}", hex::encode(raw)),
+            Token::String(ref val) => write!(f, "{}", val),
+            Token::Array(ref tokens) => {
+                write!(f, "[")?;
+                for (i, token) in tokens.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", token)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// A decoded parameter of a function call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedParam {
+    pub name: String,
+    pub ty: String,
+    pub value: Token,
+}
+
+/// Decode ABI encoded data as a tuple of `types`.
+pub fn decode(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>, Error> {
+    // Dynamic offsets may alias, so nested arrays could decode the same words over and over. Every decoded word is
+    // charged against the words of data, a well-formed encoding never reads a word twice.
+    let mut budget = data.len() / 32;
+    decode_tuple(types, data, &mut budget)
+}
+
+fn decode_tuple(types: &[ParamType], data: &[u8], budget: &mut usize) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::with_capacity(types.len());
+    let mut offset = 0;
+    for ty in types {
+        let token = if ty.is_dynamic() {
+            let ptr = read_usize(data, offset)?;
+            decode_param(ty, data.get(ptr..).ok_or(Error::InvalidData)?, budget)?
+        } else {
+            decode_param(ty, data.get(offset..).ok_or(Error::InvalidData)?, budget)?
+        };
+        tokens.push(token);
+        offset = ty
+            .head_len()
+            .and_then(|len| offset.checked_add(len))
+            .ok_or(Error::InvalidData)?;
+    }
+    Ok(tokens)
+}
+
+fn decode_param(ty: &ParamType, data: &[u8], budget: &mut usize) -> Result<Token, Error> {
+    match *ty {
+        ParamType::FixedArray(..) => (),
+        // length word and the padded content
+        ParamType::Bytes | ParamType::String => charge(budget, (read_usize(data, 0)? + 63) / 32)?,
+        _ => charge(budget, 1)?,
+    }
+    match *ty {
+        ParamType::Address => read_word(data, 0).map(|word| Token::Address(Address::from_tvm_bytes(&word[12..]))),
+        ParamType::Bool => read_word(data, 0).map(|word| Token::Bool(word[31] != 0)),
+        ParamType::Int(_) => read_word(data, 0).map(|word| Token::Int(U256::from_big_endian(word))),
+        ParamType::Uint(_) => read_word(data, 0).map(|word| Token::Uint(U256::from_big_endian(word))),
+        ParamType::FixedBytes(len) if len <= 32 => {
+            read_word(data, 0).map(|word| Token::FixedBytes(word[..len].to_vec()))
+        }
+        ParamType::FixedBytes(_) => Err(Error::InvalidData),
+        ParamType::Bytes => read_bytes(data).map(|raw| Token::Bytes(raw.to_vec())),
+        ParamType::String => read_bytes(data).map(|raw| Token::String(String::from_utf8_lossy(raw).into_owned())),
+        ParamType::Array(ref inner) => {
+            let len = read_usize(data, 0)?;
+            // every element takes at least one word, guard against malicious length
+            if len > data.len() / 32 {
+                return Err(Error::InvalidData);
+            }
+            let types = vec![(**inner).clone(); len];
+            decode_tuple(&types, &data[32..], budget).map(Token::Array)
+        }
+        ParamType::FixedArray(ref inner, len) => {
+            // every element takes at least one word, guard against malicious length
+            if len > MAX_FIXED_ARRAY_LEN || len > data.len() / 32 {
+                return Err(Error::InvalidData);
+            }
+            let types = vec![(**inner).clone(); len];
+            decode_tuple(&types, data, budget).map(Token::Array)
+        }
+    }
+}
+
+fn charge(budget: &mut usize, words: usize) -> Result<(), Error> {
+    *budget = budget.checked_sub(words).ok_or(Error::InvalidData)?;
+    Ok(())
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<&[u8], Error> {
+    data.get(offset..offset + 32).ok_or(Error::InvalidData)
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Result<usize, Error> {
+    let word = read_word(data, offset)?;
+    let val = U256::from_big_endian(word);
+    if val > U256::from(u32::MAX) {
+        return Err(Error::InvalidData);
+    }
+    Ok(val.low_u64() as usize)
+}
+
+fn read_bytes(data: &[u8]) -> Result<&[u8], Error> {
+    let len = read_usize(data, 0)?;
+    data.get(32..32 + len).ok_or(Error::InvalidData)
+}
+
+/// Canonical type name used in signatures.
+fn canonical_type(ty: &str) -> &str {
+    match ty {
+        "int" => "int256",
+        "uint" => "uint256",
+        _ => ty,
+    }
+}
+
+/// Signature of an ABI entry, like `Transfer(address,address,uint256)`.
+pub fn entry_signature(entry: &Entry) -> String {
+    let types = entry
+        .inputs
+        .iter()
+        .map(|param| canonical_type(&param.r#type))
+        .collect::<Vec<_>>();
+    format!("{}({})", entry.name, types.join(","))
+}
+
+/// Selector of a function, the first 4 bytes of the signature hash.
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&keccak256(signature.as_bytes()).as_bytes()[..4]);
+    selector
+}
+
+/// Function entries of an ABI, indexed by selector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionTable {
+    entries: Vec<Entry>,
+    selectors: HashMap<[u8; 4], usize>,
+}
+
+impl FunctionTable {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        let selectors = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.r#type == EntryType::Function as i32)
+            .map(|(i, entry)| (function_selector(&entry_signature(entry)), i))
+            .collect();
+        FunctionTable { entries, selectors }
+    }
+
+    /// ABI entries the table is built from.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Find the function entry matching the selector of call data.
+    pub fn find(&self, selector: &[u8]) -> Option<&Entry> {
+        if selector.len() != 4 {
+            return None;
+        }
+        let mut key = [0u8; 4];
+        key.copy_from_slice(selector);
+        self.selectors.get(&key).map(|&i| &self.entries[i])
+    }
+}
+
+lazy_static! {
+    /// Selectors of well-known TRC20 functions, computed once.
+    pub static ref TRC20_FUNCTIONS: FunctionTable = FunctionTable::new(trc20_functions());
+}
+
+/// Well-known TRC20 functions, used when the contract ABI is unknown.
+pub fn trc20_functions() -> Vec<Entry> {
+    let param = |name: &str, ty: &str| Param {
+        name: name.into(),
+        r#type: ty.into(),
+        ..Default::default()
+    };
+    let function = |name: &str, inputs: Vec<Param>| Entry {
+        name: name.into(),
+        inputs,
+        r#type: EntryType::Function as i32,
+        ..Default::default()
+    };
+    vec![
+        function("transfer", vec![param("_to", "address"), param("_value", "uint256")]),
+        function("approve", vec![param("_spender", "address"), param("_value", "uint256")]),
+        function(
+            "transferFrom",
+            vec![
+                param("_from", "address"),
+                param("_to", "address"),
+                param("_value", "uint256"),
+            ],
+        ),
+    ]
+}
+
+/// Decode call data of a function, without the leading selector.
+pub fn decode_function_input(entry: &Entry, data: &[u8]) -> Result<Vec<DecodedParam>, Error> {
+    let types = entry
+        .inputs
+        .iter()
+        .map(|param| ParamType::parse(&param.r#type))
+        .collect::<Result<Vec<_>, _>>()?;
+    let tokens = decode(&types, data)?;
+    Ok(entry
+        .inputs
+        .iter()
+        .zip(tokens)
+        .map(|(param, value)| DecodedParam {
+            name: param.name.clone(),
+            ty: param.r#type.clone(),
+            value,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_param_type() {
+        assert_eq!(ParamType::parse("uint256"), Ok(ParamType::Uint(256)));
+        assert_eq!(ParamType::parse("trcToken"), Ok(ParamType::Uint(256)));
+        assert_eq!(ParamType::parse("bytes32"), Ok(ParamType::FixedBytes(32)));
+        assert_eq!(
+            ParamType::parse("address[2][]"),
+            Ok(ParamType::Array(Box::new(ParamType::FixedArray(
+                Box::new(ParamType::Address),
+                2
+            ))))
+        );
+        assert!(ParamType::parse("tuple").is_err());
+        assert!(ParamType::parse("uint256[0]").is_err());
+        assert!(ParamType::parse("uint256[1025]").is_err());
+        assert!(ParamType::parse("uint256[1024][1024][1024][1024][1024][1024][1024]").is_err());
+        assert!(ParamType::parse("uint256[][][][][][][][]").is_ok());
+        assert!(ParamType::parse("uint256[][][][][][][][][]").is_err());
+    }
+
+    #[test]
+    fn test_decode_dynamic() {
+        // (uint256, string, int8) = (1, "hello", -1)
+        let data = hex::decode(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000060",
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "0000000000000000000000000000000000000000000000000000000000000005",
+            "68656c6c6f000000000000000000000000000000000000000000000000000000",
+        ))
+        .unwrap();
+        let tokens = decode(&[ParamType::Uint(256), ParamType::String, ParamType::Int(8)], &data).unwrap();
+        assert_eq!(tokens[0], Token::Uint(1.into()));
+        assert_eq!(tokens[1], Token::String("hello".into()));
+        assert_eq!(tokens[2].to_string(), "-1");
+        assert_eq!(decode(&[ParamType::String], &data[..64]), Err(Error::InvalidData));
+    }
+
+    #[test]
+    fn test_decode_aliased_offsets() {
+        // uint256[][] = [[1], []]
+        let data = hex::decode(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "0000000000000000000000000000000000000000000000000000000000000080",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        ))
+        .unwrap();
+        let ty = ParamType::parse("uint256[][]").unwrap();
+        assert_eq!(decode(&[ty], &data).unwrap()[0].to_string(), "[[1],[]]");
+
+        // every offset and length is 0x20, all levels alias the same words, about 32^8 tokens without a budget
+        let mut word = [0u8; 32];
+        word[31] = 0x20;
+        let data = word.repeat(64);
+        let ty = ParamType::parse("uint256[][][][][][][][]").unwrap();
+        assert_eq!(decode(&[ty], &data), Err(Error::InvalidData));
+    }
+
+    #[test]
+    fn test_decode_trc20_transfer_call() {
+        assert_eq!(hex::encode(function_selector("transfer(address,uint256)")), "a9059cbb");
+        assert_eq!(hex::encode(function_selector("approve(address,uint256)")), "095ea7b3");
+        assert_eq!(hex::encode(function_selector("transferFrom(address,address,uint256)")), "23b872dd");
+
+        let data = hex::decode(concat!(
+            "a9059cbb",
+            "00000000000000000000000096a3bace5adacf637eb7cc79d5787f4247da4bbe",
+            "00000000000000000000000000000000000000000000000000000000000f4240",
+        ))
+        .unwrap();
+        let entry = TRC20_FUNCTIONS.find(&data[..4]).unwrap();
+        assert_eq!(entry_signature(entry), "transfer(address,uint256)");
+        let params = decode_function_input(entry, &data[4..]).unwrap();
+        assert_eq!(params[0].name, "_to");
+        assert_eq!(params[0].value.to_string(), "TPhiVyQZ5xyvVK2KS2LTke8YvXJU5wxnbN");
+        assert_eq!(params[1].value.to_string(), "1000000");
+        assert!(TRC20_FUNCTIONS.find(&[0u8; 4]).is_none());
+    }
+}
@@ -1,2 +1,3 @@
+pub mod peer;
 pub mod protocol;
 pub mod server;
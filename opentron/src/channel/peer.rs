@@ -0,0 +1,187 @@
+//! Connected channel peers, banned addresses and static peers added at runtime.
+
+use futures::channel::oneshot;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::RwLock;
+
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub remote_addr: SocketAddr,
+    /// Connection is accepted by the passive channel service.
+    pub inbound: bool,
+    /// Connected timestamp, in ms.
+    pub connected_at: i64,
+    /// Highest block number known to the peer.
+    pub head_block_number: i64,
+    /// Round trip time of the latest ping, in ms.
+    pub latency: Option<u32>,
+    /// Is syncing blocks from the peer.
+    pub syncing: bool,
+}
+
+struct Peer {
+    info: PeerInfo,
+    done: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+pub struct PeerSet {
+    peers: RwLock<HashMap<SocketAddr, Peer>>,
+    banned: RwLock<HashSet<IpAddr>>,
+    static_nodes: RwLock<Vec<String>>,
+}
+
+impl PeerSet {
+    /// Register a connected peer, the returned receiver resolves when the peer is asked to disconnect.
+    pub fn add(&self, info: PeerInfo) -> oneshot::Receiver<()> {
+        let (done, rx) = oneshot::channel();
+        self.peers
+            .write()
+            .unwrap()
+            .insert(info.remote_addr, Peer { info, done });
+        rx
+    }
+
+    pub fn remove(&self, addr: &SocketAddr) {
+        self.peers.write().unwrap().remove(addr);
+    }
+
+    pub fn update<F: FnOnce(&mut PeerInfo)>(&self, addr: &SocketAddr, f: F) {
+        if let Some(peer) = self.peers.write().unwrap().get_mut(addr) {
+            f(&mut peer.info);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_connected(&self, addr: &SocketAddr) -> bool {
+        self.peers.read().unwrap().contains_key(addr)
+    }
+
+    /// All connected peers, ordered by address.
+    pub fn list(&self) -> Vec<PeerInfo> {
+        let mut peers: Vec<_> = self
+            .peers
+            .read()
+            .unwrap()
+            .values()
+            .map(|peer| peer.info.clone())
+            .collect();
+        peers.sort_by_key(|info| info.remote_addr);
+        peers
+    }
+
+    /// Ask a peer to disconnect, returns false if the peer is not connected.
+    pub fn disconnect(&self, addr: &SocketAddr) -> bool {
+        match self.peers.write().unwrap().remove(addr) {
+            Some(peer) => {
+                let _ = peer.done.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn disconnect_all(&self) {
+        for (_, peer) in self.peers.write().unwrap().drain() {
+            let _ = peer.done.send(());
+        }
+    }
+
+    /// Ban an IP address, connected peers from the address are disconnected.
+    pub fn ban(&self, ip: IpAddr) {
+        self.banned.write().unwrap().insert(ip);
+        let mut peers = self.peers.write().unwrap();
+        let addrs: Vec<_> = peers.keys().filter(|addr| addr.ip() == ip).cloned().collect();
+        for addr in addrs {
+            if let Some(peer) = peers.remove(&addr) {
+                let _ = peer.done.send(());
+            }
+        }
+    }
+
+    /// Returns false if the address is not banned.
+    pub fn unban(&self, ip: &IpAddr) -> bool {
+        self.banned.write().unwrap().remove(ip)
+    }
+
+    pub fn is_banned(&self, ip: &IpAddr) -> bool {
+        self.banned.read().unwrap().contains(ip)
+    }
+
+    pub fn banned(&self) -> Vec<IpAddr> {
+        let mut banned: Vec<_> = self.banned.read().unwrap().iter().cloned().collect();
+        banned.sort();
+        banned
+    }
+
+    /// Add a static peer, connected by the active channel service.
+    pub fn add_static_node(&self, addr: String) -> bool {
+        let mut nodes = self.static_nodes.write().unwrap();
+        if nodes.contains(&addr) {
+            false
+        } else {
+            nodes.push(addr);
+            true
+        }
+    }
+
+    pub fn static_nodes(&self) -> Vec<String> {
+        self.static_nodes.read().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer_info(addr: &str) -> PeerInfo {
+        PeerInfo {
+            remote_addr: addr.parse().unwrap(),
+            inbound: false,
+            connected_at: 0,
+            head_block_number: 0,
+            latency: None,
+            syncing: true,
+        }
+    }
+
+    #[test]
+    fn test_ban_disconnects_peers() {
+        let peers = PeerSet::default();
+        let mut done1 = peers.add(peer_info("10.0.0.1:18888"));
+        let mut done2 = peers.add(peer_info("10.0.0.1:18889"));
+        let mut done3 = peers.add(peer_info("10.0.0.2:18888"));
+        assert_eq!(peers.len(), 3);
+
+        peers.ban("10.0.0.1".parse().unwrap());
+        assert_eq!(peers.len(), 1);
+        assert_eq!(done1.try_recv(), Ok(Some(())));
+        assert_eq!(done2.try_recv(), Ok(Some(())));
+        assert_eq!(done3.try_recv(), Ok(None));
+        assert!(peers.is_banned(&"10.0.0.1".parse().unwrap()));
+
+        assert!(peers.unban(&"10.0.0.1".parse().unwrap()));
+        assert!(peers.banned().is_empty());
+    }
+
+    #[test]
+    fn test_update_peer() {
+        let peers = PeerSet::default();
+        let addr = "10.0.0.1:18888".parse().unwrap();
+        let _done = peers.add(peer_info("10.0.0.1:18888"));
+        peers.update(&addr, |info| info.head_block_number = 100);
+        assert_eq!(peers.list()[0].head_block_number, 100);
+
+        assert!(peers.disconnect(&addr));
+        assert!(!peers.disconnect(&addr));
+        assert!(peers.is_empty());
+    }
+}
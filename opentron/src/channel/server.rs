@@ -1,3 +1,4 @@
+use super::peer::PeerInfo;
 use super::protocol::{ChannelMessage, ChannelMessageCodec};
 use chain::IndexedBlock;
 use chrono::Utc;
@@ -27,11 +28,14 @@ use tokio::stream::StreamExt;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
 use tokio::time::Duration;
-use tokio::time::{delay_for, timeout};
+use tokio::time::{delay_for, interval, timeout, Instant};
 
 use crate::context::AppContext;
 use crate::util::block_hash_to_number;
 
+/// Interval of pinging a peer, to measure latency.
+const PING_INTERVAL: Duration = Duration::from_secs(60);
+
 pub async fn channel_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
     let config = &ctx.config.protocol.channel;
 
@@ -87,7 +91,7 @@ async fn passive_channel_service(
                             Some(Ok(sock)) => {
                                 let ctx = ctx.clone();
                                 tokio::spawn(async move {
-                                    let _ = handshake_handler(ctx, sock).await;
+                                    let _ = handshake_handler(ctx, sock, true).await;
                                 });
                             },
                             Some(Err(e)) => error!("accept failed = {:?}", e),
@@ -116,39 +120,50 @@ async fn active_channel_service(ctx: Arc<AppContext>) -> Result<(), Box<dyn Erro
 
     let active_service = {
         let ctx = ctx.clone();
-        let active_nodes = ctx.config.protocol.channel.active_nodes.clone();
         tokio::spawn(async move {
-            for peer_addr in active_nodes.into_iter().cycle() {
-                while ctx.num_active_connections.load(Ordering::SeqCst) >= max_active_connections {
+            'outer: loop {
+                // static peers may be added at runtime via the admin API
+                let mut active_nodes = ctx.config.protocol.channel.active_nodes.clone();
+                active_nodes.extend(ctx.peers.static_nodes());
+                if active_nodes.is_empty() {
                     delay_for(Duration::from_secs(2)).await;
                 }
-                if !ctx.running.load(Ordering::Relaxed) {
-                    warn!("active connection service closed");
-                    break;
+                for peer_addr in active_nodes {
+                    while ctx.num_active_connections.load(Ordering::SeqCst) >= max_active_connections {
+                        delay_for(Duration::from_secs(2)).await;
+                    }
+                    if !ctx.running.load(Ordering::Relaxed) {
+                        warn!("active connection service closed");
+                        break 'outer;
+                    }
+                    ctx.db.await_background_jobs();
+                    if !ctx.running.load(Ordering::Relaxed) {
+                        warn!("active connection service closed");
+                        break 'outer;
+                    }
+                    info!("active connection to {}", peer_addr);
+                    let ctx = ctx.clone();
+                    if let Ok(conn) = timeout(Duration::from_secs(10), TcpStream::connect(&peer_addr)).await {
+                        match conn {
+                            Ok(sock) => {
+                                ctx.num_active_connections.fetch_add(1, Ordering::SeqCst);
+                                tokio::spawn(async move {
+                                    let _ = handshake_handler(ctx.clone(), sock, false).await;
+                                    ctx.num_active_connections.fetch_sub(1, Ordering::SeqCst);
+                                });
+                            }
+                            Err(e) => {
+                                warn!("connect {} failed: {}", peer_addr, e);
+                            }
+                        }
+                    } else {
+                        warn!("connect timeout");
+                    }
                 }
-                ctx.db.await_background_jobs();
                 if !ctx.running.load(Ordering::Relaxed) {
                     warn!("active connection service closed");
                     break;
                 }
-                info!("active connection to {}", peer_addr);
-                let ctx = ctx.clone();
-                if let Ok(conn) = timeout(Duration::from_secs(10), TcpStream::connect(&peer_addr)).await {
-                    match conn {
-                        Ok(sock) => {
-                            ctx.num_active_connections.fetch_add(1, Ordering::SeqCst);
-                            tokio::spawn(async move {
-                                let _ = handshake_handler(ctx.clone(), sock).await;
-                                ctx.num_active_connections.fetch_sub(1, Ordering::SeqCst);
-                            });
-                        }
-                        Err(e) => {
-                            warn!("connect {} failed: {}", peer_addr, e);
-                        }
-                    }
-                } else {
-                    warn!("connect timeout");
-                }
             }
         })
     };
@@ -156,15 +171,26 @@ async fn active_channel_service(ctx: Arc<AppContext>) -> Result<(), Box<dyn Erro
     Ok(())
 }
 
-async fn handshake_handler(ctx: Arc<AppContext>, sock: TcpStream) -> Result<(), Box<dyn Error>> {
+async fn handshake_handler(ctx: Arc<AppContext>, sock: TcpStream, inbound: bool) -> Result<(), Box<dyn Error>> {
     let peer_addr = sock.peer_addr()?;
+    if ctx.peers.is_banned(&peer_addr.ip()) {
+        warn!("reject banned peer {}", peer_addr);
+        return Ok(());
+    }
     let logger = slog_scope::logger().new(o!(
         "peer_addr" => peer_addr,
     ));
-    inner_handshake_handler(ctx, sock).with_logger(logger).await
+    inner_handshake_handler(ctx, sock, peer_addr, inbound)
+        .with_logger(logger)
+        .await
 }
 
-async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> Result<(), Box<dyn Error>> {
+async fn inner_handshake_handler(
+    ctx: Arc<AppContext>,
+    mut sock: TcpStream,
+    peer_addr: SocketAddr,
+    inbound: bool,
+) -> Result<(), Box<dyn Error>> {
     let (reader, writer) = sock.split();
 
     let mut reader = ChannelMessageCodec::new_read(reader);
@@ -265,12 +291,21 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
                     peer_head_block_id.as_ref().unwrap().number >= head_block_id.as_ref().unwrap().number;
 
                 info!("handshake finished, need sync = {}", need_syncing);
+                let done = ctx.peers.add(PeerInfo {
+                    remote_addr: peer_addr,
+                    inbound,
+                    connected_at: Utc::now().timestamp_millis(),
+                    head_block_number: peer_head_block_id.as_ref().unwrap().number,
+                    latency: None,
+                    syncing: need_syncing,
+                });
                 let logger = slog_scope::logger().new(o!(
                     "protocol" => "channel"
                 ));
-                let ret = sync_channel_handler(ctx, need_syncing, reader, writer)
+                let ret = sync_channel_handler(ctx.clone(), peer_addr, done, need_syncing, reader, writer)
                     .with_logger(logger)
                     .await;
+                ctx.peers.remove(&peer_addr);
                 match ret {
                     Ok(_) => info!("channel finished"),
                     Err(e) => warn!("channel finished with error={:?}", e),
@@ -302,6 +337,8 @@ async fn inner_handshake_handler(ctx: Arc<AppContext>, mut sock: TcpStream) -> R
 
 async fn sync_channel_handler(
     ctx: Arc<AppContext>,
+    peer_addr: SocketAddr,
+    done: oneshot::Receiver<()>,
     mut syncing: bool,
     mut reader: impl Stream<Item = Result<ChannelMessage, io::Error>> + Unpin,
     mut writer: impl Sink<ChannelMessage, Error = io::Error> + Unpin,
@@ -309,12 +346,7 @@ async fn sync_channel_handler(
     let config = &ctx.config.protocol.channel;
     let batch_size = config.sync_batch_size;
 
-    let mut done = {
-        let mut peers = ctx.peers.write().unwrap();
-        let (tx, rx) = oneshot::channel::<()>();
-        peers.push(tx);
-        rx.fuse()
-    };
+    let mut done = done.fuse();
 
     let highest_block = ctx.db.get_block_by_number(ctx.db.get_block_height() as u64).ok();
     let highest_block_id = highest_block
@@ -341,8 +373,11 @@ async fn sync_channel_handler(
     }
 
     let mut syncing_block_ids: Vec<Vec<u8>> = vec![];
-    let mut pinged = false;
+    let mut ping_sent_at: Option<Instant> = None;
+    let mut ping_interval = interval(PING_INTERVAL);
     let (mut tx, mut rx) = mpsc::channel::<ChannelMessage>(1000);
+    let mut pending_txns = ctx.mempool.subscribe();
+    let mut produced_blocks = ctx.produced_blocks.subscribe();
 
     loop {
         let mut next_packet = reader.next().fuse();
@@ -350,15 +385,22 @@ async fn sync_channel_handler(
         let mut timeout = delay_for(Duration::from_secs(18)).fuse();
         select! {
             _ = timeout => {
-                if !pinged {
+                if ping_sent_at.is_none() {
                     warn!("timeout, try ping remote");
                     writer.send(ChannelMessage::Ping).await?;
-                    pinged = true;
+                    ping_sent_at = Some(Instant::now());
                 } else {
                     warn!("timeout without replying to ping");
                     return Ok(());
                 }
             }
+            _ = ping_interval.tick().fuse() => {
+                // measure latency
+                if ping_sent_at.is_none() {
+                    writer.send(ChannelMessage::Ping).await?;
+                    ping_sent_at = Some(Instant::now());
+                }
+            }
             _ = done => {
                 warn!("close channel connection");
                 break;
@@ -388,6 +430,10 @@ async fn sync_channel_handler(
                     },
                     Ok(ChannelMessage::Pong) => {
                         debug!("pong");
+                        if let Some(sent_at) = ping_sent_at.take() {
+                            let latency = sent_at.elapsed().as_millis() as u32;
+                            ctx.peers.update(&peer_addr, |info| info.latency = Some(latency));
+                        }
                     },
                     Ok(ChannelMessage::TransactionInventory(inv)) => {
                         let Inventory { mut ids, r#type } = inv;
@@ -406,6 +452,16 @@ async fn sync_channel_handler(
                     }
                     Ok(ChannelMessage::FetchTransactionInventory(inv)) => {
                         info!("fetch transactions {:?}", inv);
+                        let transactions: Vec<_> = inv
+                            .ids
+                            .iter()
+                            .filter(|id| id.len() == 32)
+                            .filter_map(|id| ctx.mempool.get(&H256::from_slice(id)))
+                            .map(|txn| txn.raw)
+                            .collect();
+                        if !transactions.is_empty() {
+                            tx.send(ChannelMessage::Transactions(Transactions { transactions })).await?;
+                        }
                     }
                     Ok(ChannelMessage::Transactions(Transactions { transactions })) => {
                         for txn in &transactions {
@@ -447,6 +503,8 @@ async fn sync_channel_handler(
                             chain_inv.remain_num);
 
                         last_block_number = last_block_id.number;
+                        let remote_head_number = last_block_number + chain_inv.remain_num;
+                        ctx.peers.update(&peer_addr, |info| info.head_block_number = remote_head_number);
 
                         let tail = if syncing_block_ids.len() >= batch_size {
                             syncing_block_ids.split_off(batch_size)
@@ -457,6 +515,7 @@ async fn sync_channel_handler(
                             info!("syncing finished, entering gossip loop");
                             // remore: peer.setNeedSyncFromUs = false
                             syncing = false;
+                            ctx.peers.update(&peer_addr, |info| info.syncing = false);
                         } else {
                             last_block_number_in_this_batch = block_hash_to_number(syncing_block_ids.last().unwrap());
                         }
@@ -469,6 +528,10 @@ async fn sync_channel_handler(
                     }
                     Ok(ChannelMessage::Block(block)) => {
                         let block = IndexedBlock::from_raw(block);
+                        let block_number = block.number();
+                        ctx.peers.update(&peer_addr, |info| {
+                            info.head_block_number = info.head_block_number.max(block_number)
+                        });
                         if !ctx.recent_blk_ids.read().unwrap().contains(&block.header.hash) {
                             if syncing {
                                 if block.number() % 100 == 0 {
@@ -494,6 +557,7 @@ async fn sync_channel_handler(
                             if !ctx.db.has_block(&block)  {
                                 ctx.db.insert_block(&block)?;
                                 ctx.db.update_block_height(block.number());
+                                ctx.mempool.remove_included(&block);
                             } else {
                                 warn!("block exists in db");
                             }
@@ -590,6 +654,30 @@ async fn sync_channel_handler(
                     },
                 }
             }
+            txn_id = pending_txns.recv().fuse() => {
+                // announce pending transactions broadcasted via API
+                if let Ok(txn_id) = txn_id {
+                    if !syncing {
+                        let inv = Inventory {
+                            r#type: 0, // TRX
+                            ids: vec![txn_id.as_bytes().to_vec()],
+                        };
+                        writer.send(ChannelMessage::TransactionInventory(inv)).await?;
+                    }
+                }
+            }
+            blk_id = produced_blocks.recv().fuse() => {
+                // announce blocks produced by this node
+                if let Ok(blk_id) = blk_id {
+                    if !syncing {
+                        let inv = Inventory {
+                            r#type: 1, // BLOCK
+                            ids: vec![blk_id.as_bytes().to_vec()],
+                        };
+                        writer.send(ChannelMessage::BlockInventory(inv)).await?;
+                    }
+                }
+            }
             // select!
             packet = sending_packet => {
                 if let Some(msg) = packet {
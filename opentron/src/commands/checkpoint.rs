@@ -0,0 +1,58 @@
+//! RocksDB checkpoints, consistent copies of the db, as backups.
+//!
+//! Running nodes create checkpoints by the admin API, since the db is locked by the node.
+
+use clap::ArgMatches;
+use log::info;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::db::ChainDB;
+
+/// Restore the data dir from a checkpoint. SST files are immutable and hard-linked when possible, other files like
+/// MANIFEST are appended by RocksDB, so they are copied.
+fn restore<P: AsRef<Path>, Q: AsRef<Path>>(checkpoint_dir: P, data_dir: Q) -> Result<(), Box<dyn Error>> {
+    let data_dir = data_dir.as_ref();
+    if data_dir.exists() && fs::read_dir(data_dir)?.next().is_some() {
+        return Err("data dir is not empty".into());
+    }
+    fs::create_dir_all(data_dir)?;
+
+    for entry in fs::read_dir(checkpoint_dir)? {
+        let path = entry?.path();
+        let target = data_dir.join(path.file_name().unwrap());
+        let is_sst = path.extension().map(|ext| ext == "sst").unwrap_or(false);
+        if !is_sst || fs::hard_link(&path, &target).is_err() {
+            fs::copy(&path, &target)?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(config_path)?;
+    info!("config file loaded");
+
+    match matches.subcommand() {
+        ("create", Some(arg_matches)) => {
+            let checkpoint_dir = arg_matches.value_of("DIR").unwrap();
+            let db = ChainDB::new(&config.storage.data_dir);
+            info!("db opened");
+            db.create_checkpoint(checkpoint_dir)?;
+            info!("checkpoint created => {}", checkpoint_dir);
+        }
+        ("restore", Some(arg_matches)) => {
+            let checkpoint_dir = arg_matches.value_of("DIR").unwrap();
+            restore(checkpoint_dir, &config.storage.data_dir)?;
+            info!("data dir restored from checkpoint => {}", checkpoint_dir);
+            // verify the restored db
+            let db = ChainDB::new(&config.storage.data_dir);
+            info!("block height = {}", db.get_block_height());
+        }
+        _ => return Err("unknown checkpoint command, create or restore".into()),
+    }
+
+    Ok(())
+}
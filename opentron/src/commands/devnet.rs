@@ -0,0 +1,107 @@
+//! Single node local devnet, like ganache or anvil.
+//!
+//! Blocks are produced every 3s by one local witness, without any peers. Test accounts are derived
+//! deterministically, and allocated balances in the generated genesis. There is no account state without an
+//! executor, so the balances are only genesis allocs, they can not be queried, and are not checked or spent by
+//! transactions.
+
+use chrono::Utc;
+use clap::ArgMatches;
+use keys::{Address, Private};
+use log::info;
+use std::error::Error;
+use std::path::Path;
+
+use crate::config::{Config, WitnessConfig};
+use crate::constants::BLOCK_PRODUCING_INTERVAL;
+use crate::context::AppContext;
+use crate::db::ChainDB;
+use crate::genesis::{Alloc, GenesisConfig, Witness};
+
+const DEFAULT_NUM_OF_ACCOUNTS: usize = 10;
+/// 1_000_000 TRX, in SUN.
+const TEST_ACCOUNT_BALANCE: i64 = 1_000_000_000_000;
+
+/// Deterministic private key of a test account.
+fn test_key(name: &str) -> Private {
+    Private::from(crypto::sha256(format!("opentron-devnet-{}", name).as_bytes()).to_fixed_bytes())
+}
+
+/// Build context of a devnet node, from the config file with p2p services disabled.
+pub fn init_context<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<AppContext, Box<dyn Error>> {
+    let mut config = Config::load_from_file(config_path)?;
+
+    let num_accounts = matches
+        .value_of("accounts")
+        .map(|val| val.parse())
+        .transpose()?
+        .unwrap_or(DEFAULT_NUM_OF_ACCOUNTS);
+    let data_dir = matches.value_of("data-dir").expect("has default in cli.yml; qed");
+
+    // reuse genesis timestamp of an existing devnet
+    let timestamp = {
+        let db = ChainDB::new(data_dir);
+        db.get_genesis_block()
+            .map(|blk| blk.header.timestamp())
+            .unwrap_or_else(|_| {
+                let now = Utc::now().timestamp_millis();
+                now - now % BLOCK_PRODUCING_INTERVAL as i64
+            })
+    };
+
+    let witness_key = test_key("witness");
+    let witness_address = Address::from_private(&witness_key);
+    let accounts: Vec<_> = (0..num_accounts)
+        .map(|i| test_key(&i.to_string()))
+        .map(|key| (Address::from_private(&key), key))
+        .collect();
+
+    let witnesses = vec![Witness {
+        address: witness_address.to_string(),
+        url: "http://devnet.local".into(),
+        votes: 100_000_000,
+    }];
+    let allocs = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, (addr, _))| Alloc {
+            address: addr.to_string(),
+            name: format!("Account{}", i),
+            balance: TEST_ACCOUNT_BALANCE,
+        })
+        .collect();
+    let genesis_config = GenesisConfig::new(timestamp, witnesses, allocs);
+
+    config.storage.data_dir = data_dir.into();
+    config.protocol.discovery.enable = false;
+    config.protocol.channel.enable = false;
+    // no peer to connect to, ready once the head block is recent
+    config.graphql.ready_min_peers = Some(0);
+    config.witness = WitnessConfig {
+        enable: true,
+        private_key: witness_key.to_string(),
+        need_sync_check: false,
+    };
+
+    let mut ctx = AppContext::new(config, genesis_config)?;
+    // never query outbound ip, devnet runs offline
+    ctx.outbound_ip = "127.0.0.1".into();
+    info!("devnet data dir => {}", data_dir);
+
+    println!("Witness");
+    println!("=======");
+    println!("{} {}", witness_address, witness_key);
+    println!();
+    println!("Test Accounts");
+    println!("=============");
+    println!(
+        "Allocated {} TRX each in genesis, balances are not tracked, no account state without an executor",
+        TEST_ACCOUNT_BALANCE / 1_000_000
+    );
+    for (i, (addr, key)) in accounts.iter().enumerate() {
+        println!("({}) {} {}", i, addr, key);
+    }
+    println!();
+
+    Ok(ctx)
+}
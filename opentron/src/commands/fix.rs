@@ -25,5 +25,10 @@ pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> R
         db.handle_chain_fork_at(block_number, /* dry_run */ false)?;
     }
 
+    if matches.is_present("contracts") {
+        db.backfill_smart_contracts()?;
+        info!("smart contracts backfilled");
+    }
+
     Ok(())
 }
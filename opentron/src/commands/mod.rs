@@ -1,2 +1,6 @@
 pub mod check;
+pub mod checkpoint;
+pub mod devnet;
 pub mod fix;
+pub mod replay;
+pub mod snapshot;
@@ -0,0 +1,101 @@
+//! Rebuild the contract index of stored blocks into a separate state db, to verify it against known-good checksums.
+//!
+//! This is not execution replay. Only the contract index, smart contracts created by blocks, is rebuilt, account state
+//! and execution results need an executor.
+//!
+//! Checksums are of OpenTron's state db layout, see `ChainDB::state_checksum`, so known-good checksums must come from
+//! another OpenTron replay, not java-tron. Blocks are read from the data dir, a snapshot is replayed by importing it.
+
+use clap::ArgMatches;
+use log::{error, info};
+use primitive_types::H256;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::db::ChainDB;
+
+/// Load known-good checksums, `<block_number> <checksum>` per line, `#` for comments.
+fn load_checksums<P: AsRef<Path>>(path: P) -> Result<BTreeMap<u64, H256>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut checksums = BTreeMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some(num), Some(checksum)) => {
+                let checksum = hex::decode(checksum.trim_start_matches("0x"))?;
+                if checksum.len() != 32 {
+                    return Err(Box::new(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("malformed checksum: {}", line),
+                    )));
+                }
+                checksums.insert(num.parse()?, H256::from_slice(&checksum));
+            }
+            _ => return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, format!("malformed line: {}", line)))),
+        }
+    }
+    Ok(checksums)
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(config_path)?;
+    info!("config file loaded");
+    let db = ChainDB::new(&config.storage.data_dir);
+    info!("db opened");
+
+    let state_dir = matches.value_of("state-dir").expect("has default in cli.yml; qed");
+    let state_db = ChainDB::new(state_dir);
+    info!("state db opened => {}", state_dir);
+
+    let interval = matches
+        .value_of("interval")
+        .expect("has default in cli.yml; qed")
+        .parse::<u64>()?
+        .max(1);
+    let to_block = match matches.value_of("to") {
+        Some(val) => val.parse()?,
+        None => db.get_block_height() as u64,
+    };
+    let checksums = matches
+        .value_of("compare")
+        .map(load_checksums)
+        .transpose()?
+        .unwrap_or_default();
+
+    // the state db records replayed height as its block height
+    let from_block = state_db.get_block_height() as u64 + 1;
+    info!("replay block {} => {}", from_block, to_block);
+
+    for num in from_block..=to_block {
+        let block = db.get_block_by_number(num)?;
+        state_db.replay_block(&block)?;
+
+        let expected = checksums.get(&num);
+        if num % interval == 0 || num == to_block || expected.is_some() {
+            state_db.force_update_block_height(num as i64)?;
+            // replayed blocks never fork
+            state_db.prune_undo_journals(num as i64)?;
+
+            let checksum = state_db.state_checksum();
+            // same format as the compare file
+            println!("{} {}", num, hex::encode(checksum.as_bytes()));
+            if let Some(expected) = expected {
+                if *expected != checksum {
+                    error!("❌ state checksum mismatch at block {}, expected {:?}", num, expected);
+                    return Err(Box::new(io::Error::new(io::ErrorKind::InvalidData, "state checksum mismatch")));
+                }
+                info!("✅ state checksum matched at block {}", num);
+            }
+        }
+    }
+
+    info!("replayed to block {}", to_block);
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! Snapshot of the chain db, to spin up a new node without syncing from peers.
+//!
+//! A snapshot is a gzip stream of `MAGIC`, a JSON manifest, blocks from genesis to the solid block, and a SHA256
+//! checksum of all above. Each of manifest and blocks is prefixed by its length. Only solid blocks are exported,
+//! so an imported node never needs to roll back. State is rebuilt from blocks on import, in a single pass verifying
+//! blocks as they are read, so a corrupted snapshot leaves a partial data dir to be removed.
+
+use byteorder::{ReadBytesExt, BE};
+use chain::IndexedBlock;
+use chrono::Utc;
+use clap::ArgMatches;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::Block;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::constants::MAX_ACCEPTABLE_BLOCK_SIZE;
+use crate::db::ChainDB;
+use crate::genesis::GenesisConfig;
+use crate::producer::WitnessSchedule;
+
+const MAGIC: &[u8] = b"OPENTRON-SNAPSHOT-1";
+/// Max length of a record, the manifest or a block.
+const MAX_RECORD_SIZE: usize = MAX_ACCEPTABLE_BLOCK_SIZE;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Manifest {
+    p2p_version: i32,
+    genesis_block_hash: String,
+    head_block_number: i64,
+    head_block_hash: String,
+    /// In ms.
+    created_at: i64,
+}
+
+fn write_record<W: Write>(writer: &mut W, hasher: &mut Sha256, record: &[u8]) -> io::Result<()> {
+    let len = (record.len() as u32).to_be_bytes();
+    hasher.update(&len);
+    hasher.update(record);
+    writer.write_all(&len)?;
+    writer.write_all(record)
+}
+
+fn read_record<R: Read>(reader: &mut R, hasher: &mut Sha256) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32::<BE>()?;
+    if len as usize > MAX_RECORD_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "snapshot record too large"));
+    }
+    hasher.update(&len.to_be_bytes());
+    let mut record = vec![0u8; len as usize];
+    reader.read_exact(&mut record)?;
+    hasher.update(&record);
+    Ok(record)
+}
+
+fn export<P: AsRef<Path>>(config: &Config, path: P) -> Result<(), Box<dyn Error>> {
+    let db = ChainDB::new(&config.storage.data_dir);
+    info!("db opened");
+
+    let head_block_number = db.get_solid_block_number();
+    // blocks above the solid block may be rolled back, and dbs synced before solidity tracking have none
+    if head_block_number == 0 && db.get_block_height() > 0 {
+        return Err("no solid block to export, wait for the node to solidify blocks".into());
+    }
+    let manifest = Manifest {
+        p2p_version: config.chain.p2p_version,
+        genesis_block_hash: hex::encode(db.get_genesis_block()?.hash()),
+        head_block_number,
+        head_block_hash: hex::encode(db.get_block_by_number(head_block_number as u64)?.hash()),
+        created_at: Utc::now().timestamp_millis(),
+    };
+    info!("export snapshot => {:?}", manifest);
+
+    let mut writer = GzEncoder::new(BufWriter::new(File::create(path)?), Compression::default());
+    let mut hasher = Sha256::new();
+    writer.write_all(MAGIC)?;
+    hasher.update(MAGIC);
+    write_record(&mut writer, &mut hasher, &serde_json::to_vec(&manifest)?)?;
+
+    let mut buf = Vec::with_capacity(1024);
+    for num in 0..=head_block_number as u64 {
+        let block = db.get_block_by_number(num)?.into_raw_block();
+        buf.clear();
+        block.encode(&mut buf)?;
+        write_record(&mut writer, &mut hasher, &buf)?;
+        if num % 10_000 == 0 {
+            info!("exported block {}", num);
+        }
+    }
+
+    let checksum = hasher.finalize();
+    writer.write_all(&checksum)?;
+    writer.finish()?.flush()?;
+    info!("snapshot exported, checksum => {}", hex::encode(&checksum));
+    Ok(())
+}
+
+/// Decode a block record, rejecting blocks missing raw data.
+fn decode_block(record: &[u8]) -> Result<IndexedBlock, Box<dyn Error>> {
+    let block = Block::decode(record)?;
+    let has_raw_header = block
+        .block_header
+        .as_ref()
+        .map(|header| header.raw_data.is_some())
+        .unwrap_or(false);
+    if !has_raw_header || block.transactions.iter().any(|txn| txn.raw_data.is_none()) {
+        return Err("malformed block in snapshot".into());
+    }
+    Ok(IndexedBlock::from_raw(block))
+}
+
+/// Reader of a snapshot file, checking blocks are linked from genesis to the head block.
+struct SnapshotReader {
+    reader: GzDecoder<BufReader<File>>,
+    hasher: Sha256,
+    manifest: Manifest,
+    next_block_number: i64,
+    parent_hash: H256,
+}
+
+impl SnapshotReader {
+    fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut reader = GzDecoder::new(BufReader::new(File::open(path)?));
+        let mut hasher = Sha256::new();
+
+        let mut magic = vec![0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err("not a snapshot file".into());
+        }
+        hasher.update(MAGIC);
+        let manifest = serde_json::from_slice(&read_record(&mut reader, &mut hasher)?)?;
+
+        Ok(SnapshotReader {
+            reader,
+            hasher,
+            manifest,
+            next_block_number: 0,
+            parent_hash: H256::zero(),
+        })
+    }
+
+    /// Next block, `None` after the head block.
+    fn next_block(&mut self) -> Result<Option<IndexedBlock>, Box<dyn Error>> {
+        let num = self.next_block_number;
+        if num > self.manifest.head_block_number {
+            return Ok(None);
+        }
+        let block = decode_block(&read_record(&mut self.reader, &mut self.hasher)?)?;
+        let raw_header = block
+            .header
+            .raw
+            .raw_data
+            .as_ref()
+            .expect("checked by decode_block; qed");
+        if block.number() != num || (num > 0 && raw_header.parent_hash != self.parent_hash.as_bytes()) {
+            return Err(format!("broken chain at block {}", num).into());
+        }
+        self.parent_hash = *block.hash();
+        self.next_block_number += 1;
+        Ok(Some(block))
+    }
+
+    /// Check the head block and the checksum, after all blocks are read.
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        if self.next_block_number <= self.manifest.head_block_number {
+            return Err("snapshot is not fully read".into());
+        }
+        if hex::encode(self.parent_hash) != self.manifest.head_block_hash {
+            return Err("head block is inconsistent with manifest".into());
+        }
+        let mut checksum = [0u8; 32];
+        self.reader.read_exact(&mut checksum)?;
+        if checksum[..] != self.hasher.finalize()[..] {
+            return Err("snapshot checksum mismatch".into());
+        }
+        Ok(())
+    }
+}
+
+/// Insert blocks while reading them, then check the checksum.
+fn import_blocks(db: &ChainDB, mut snapshot: SnapshotReader) -> Result<(), Box<dyn Error>> {
+    while let Some(block) = snapshot.next_block()? {
+        if !db.has_block(&block) {
+            db.insert_block(&block)?;
+        }
+        if block.number() % 10_000 == 0 {
+            info!("imported block {}", block.number());
+        }
+    }
+    snapshot.finish()
+}
+
+fn import<P: AsRef<Path>>(config: &Config, genesis_config: &GenesisConfig, path: P) -> Result<(), Box<dyn Error>> {
+    let snapshot = SnapshotReader::open(path.as_ref())?;
+    let manifest = &snapshot.manifest;
+    info!("import snapshot => {:?}", manifest);
+
+    if manifest.p2p_version != config.chain.p2p_version {
+        return Err(format!("snapshot of another network, p2p version {}", manifest.p2p_version).into());
+    }
+    if manifest.genesis_block_hash != hex::encode(genesis_config.to_indexed_block()?.hash()) {
+        return Err("snapshot genesis block is inconsistent with genesis config".into());
+    }
+
+    let db = ChainDB::new(&config.storage.data_dir);
+    info!("db opened");
+    if db.get_block_height() > 0 {
+        return Err("data dir is not empty".into());
+    }
+    db.set_witness_schedule(WitnessSchedule::from_genesis(
+        genesis_config,
+        config.chain.parameter.maintenance_interval,
+    )?);
+
+    let head_block_number = snapshot.manifest.head_block_number;
+    import_blocks(&db, snapshot).map_err(|e| format!("{}, please remove the data dir", e))?;
+
+    // all imported blocks are solid
+    db.update_block_height(head_block_number);
+    db.update_solid_block_number(head_block_number)?;
+    db.prune_undo_journals(head_block_number)?;
+    info!("snapshot imported, block height => {}", db.get_block_height());
+    Ok(())
+}
+
+pub async fn main<P: AsRef<Path>>(config_path: P, matches: &ArgMatches<'_>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_from_file(&config_path)?;
+    info!("config file loaded");
+
+    match matches.subcommand() {
+        ("export", Some(arg_matches)) => export(&config, arg_matches.value_of("FILE").unwrap()),
+        ("import", Some(arg_matches)) => {
+            let genesis_path = config_path.as_ref().parent().unwrap().join(&config.chain.genesis);
+            let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
+            import(&config, &genesis_config, arg_matches.value_of("FILE").unwrap())
+        }
+        _ => Err("unknown snapshot command, export or import".into()),
+    }
+}
@@ -67,6 +67,96 @@ pub struct ProtocolConfig {
 pub struct GraphQLConfig {
     pub enable: bool,
     pub endpoint: String,
+    /// Bearer token of the admin API, disabled if not set.
+    pub admin_token: Option<String>,
+    /// Max lag of the head block to wall-clock time for `/ready`, in seconds.
+    pub ready_max_block_lag: Option<i64>,
+    /// Min number of connected peers for `/ready`.
+    pub ready_min_peers: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct JsonRpcConfig {
+    pub enable: bool,
+    pub endpoint: String,
+}
+
+impl Default for JsonRpcConfig {
+    fn default() -> Self {
+        JsonRpcConfig {
+            enable: false,
+            endpoint: "127.0.0.1:8545".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct HttpApiConfig {
+    pub enable: bool,
+    pub endpoint: String,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        HttpApiConfig {
+            enable: false,
+            endpoint: "127.0.0.1:8090".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct GrpcConfig {
+    pub enable: bool,
+    pub endpoint: String,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        GrpcConfig {
+            enable: false,
+            endpoint: "127.0.0.1:50051".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct MetricsConfig {
+    pub enable: bool,
+    pub endpoint: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enable: false,
+            endpoint: "127.0.0.1:9527".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct WitnessConfig {
+    pub enable: bool,
+    /// Private key of the witness, in hex.
+    pub private_key: String,
+    /// Only produce blocks after the node is synced. Disable it to start a new chain.
+    pub need_sync_check: bool,
+}
+
+impl Default for WitnessConfig {
+    fn default() -> Self {
+        WitnessConfig {
+            enable: false,
+            private_key: String::new(),
+            need_sync_check: true,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -76,6 +166,17 @@ pub struct Config {
     pub storage: StorageConfig,
     pub protocol: ProtocolConfig,
     pub graphql: GraphQLConfig,
+    // Sections added after the initial release default to disabled, so that old config files still load.
+    #[serde(default)]
+    pub json_rpc: JsonRpcConfig,
+    #[serde(default)]
+    pub http_api: HttpApiConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub witness: WitnessConfig,
 }
 
 impl Config {
@@ -73,7 +73,7 @@ pub const ADAPTIVE_ENERGY_INCREASE_RATE_NUMERATOR: i64 = 1000;
 pub const ADAPTIVE_ENERGY_INCREASE_RATE_DENOMINATOR: i64 = 999;
 
 /// Block versions. These versions match version names on github release page(or PR numbers).
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub enum BlockVersion {
     /// Also applies to all blocks before around #2300000.
     Genesis = 0,
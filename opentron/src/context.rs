@@ -1,4 +1,3 @@
-use futures::channel::oneshot;
 use log::info;
 use primitive_types::H256;
 use proto2::common::BlockId;
@@ -7,22 +6,30 @@ use std::error::Error;
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU32};
 use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
 
+use crate::channel::peer::PeerSet;
 use crate::config::Config;
 use crate::db::ChainDB;
 use crate::genesis::GenesisConfig;
+use crate::mempool::Mempool;
+use crate::producer::WitnessSchedule;
 
 pub struct AppContext {
     pub outbound_ip: String,
     pub node_id: Vec<u8>,
     pub genesis_block_id: Option<BlockId>,
+    pub genesis_config: GenesisConfig,
     pub config: Config,
     pub db: ChainDB,
     pub running: Arc<AtomicBool>,
     pub num_active_connections: AtomicU32,
     pub recent_blk_ids: RwLock<HashSet<H256>>,
     pub syncing: RwLock<bool>,
-    pub peers: RwLock<Vec<oneshot::Sender<()>>>,
+    pub peers: PeerSet,
+    pub mempool: Mempool,
+    /// Ids of blocks produced by this node, to be announced to peers.
+    pub produced_blocks: broadcast::Sender<H256>,
 }
 
 impl AppContext {
@@ -32,9 +39,18 @@ impl AppContext {
         let genesis_path = path.as_ref().parent().unwrap().join(&config.chain.genesis);
 
         let genesis_config = GenesisConfig::load_from_file(&genesis_path)?;
+
+        Self::new(config, genesis_config)
+    }
+
+    pub fn new(config: Config, genesis_config: GenesisConfig) -> Result<Self, Box<dyn Error>> {
         let genesis_blk = genesis_config.to_indexed_block()?;
 
         let db = ChainDB::new(&config.storage.data_dir);
+        db.set_witness_schedule(WitnessSchedule::from_genesis(
+            &genesis_config,
+            config.chain.parameter.maintenance_interval,
+        )?);
 
         if !db.has_block(&genesis_blk) {
             if let Ok(_) = db.get_genesis_block() {
@@ -66,7 +82,10 @@ impl AppContext {
             num_active_connections: AtomicU32::new(0),
             recent_blk_ids: RwLock::new(HashSet::new()),
             syncing: RwLock::new(true),
-            peers: RwLock::default(),
+            peers: PeerSet::default(),
+            mempool: Mempool::default(),
+            produced_blocks: broadcast::channel(100).0,
+            genesis_config,
         })
     }
 }
@@ -1,12 +1,17 @@
 use byteorder::{ByteOrder, BE};
 use bytes::BytesMut;
 use chain::{BlockHeader, IndexedBlock, IndexedBlockHeader, IndexedTransaction, Transaction};
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use primitive_types::H256;
 use prost::Message;
 use proto2::chain::ContractType;
+use proto2::common::SmartContract;
+use proto2::contract::ClearAbiContract;
+use proto2::state::TransactionReceipt;
 use rand::Rng;
+use rocks::checkpoint::Checkpoint;
 use rocks::prelude::*;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet, LinkedList};
 use std::error::Error;
 use std::fs::OpenOptions;
@@ -14,6 +19,16 @@ use std::io;
 use std::io::Write;
 use std::iter::FromIterator;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::fork::ForkController;
+use crate::metrics;
+use crate::producer::WitnessSchedule;
+use crate::solidity::SolidityTracker;
+use crate::state::{created_smart_contract, receipt_from_block_transaction, StateColumn, StateOverlay, UndoJournal};
+use crate::util::block_hash_to_number;
 
 pub type BoxError = Box<dyn Error>;
 
@@ -24,12 +39,35 @@ pub enum CheckResult {
     BreakAt(u64),
 }
 
+/// Changes of the chain, published to subscribers of `ChainDB::subscribe`.
+#[derive(Clone, Debug)]
+pub enum ChainEvent {
+    /// A block is inserted, possibly on a fork.
+    NewBlock(Arc<IndexedBlock>),
+    /// The main chain switched to a longer fork at block number. Blocks of the shorter fork stay stored as a fork,
+    /// until purged by `handle_chain_fork_at`.
+    Reorg {
+        fork_at: u64,
+        purged_blocks: Vec<H256>,
+        new_blocks: Vec<H256>,
+        orphan_transactions: Vec<H256>,
+    },
+}
+
 pub struct ChainDB {
     db: DB,
     default: ColumnFamily,
     block_header: ColumnFamily,
     transaction: ColumnFamily,
     transaction_block: ColumnFamily,
+    transaction_receipt: ColumnFamily,
+    contract: ColumnFamily,
+    undo_journal: ColumnFamily,
+    /// Sequence number of the next undo journal.
+    next_journal_seq: AtomicU64,
+    /// Active witnesses, to track solidity of main chain blocks. Also serializes state updates of blocks.
+    witness_schedule: Mutex<Option<WitnessSchedule>>,
+    events: broadcast::Sender<ChainEvent>,
 }
 
 impl Drop for ChainDB {
@@ -79,9 +117,21 @@ impl ChainDB {
                     // .optimize_for_point_lookup(32)
                     .max_write_buffer_number(6),
             ),
+            // transaction_hash => TransactionReceipt
+            ColumnFamilyDescriptor::new(
+                "transaction-receipt",
+                ColumnFamilyOptions::default().max_write_buffer_number(6),
+            ),
+            // contract_address => SmartContract
+            ColumnFamilyDescriptor::new("contract", ColumnFamilyOptions::default()),
+            // [seq: u64] => UndoJournal, in order of block insertion
+            ColumnFamilyDescriptor::new("undo-journal", ColumnFamilyOptions::default()),
         ];
 
         let (db, mut handles) = DB::open_with_column_families(&db_options, db_path, column_families).unwrap();
+        let undo_journal = handles.pop().unwrap();
+        let contract = handles.pop().unwrap();
+        let txn_receipt = handles.pop().unwrap();
         let txn_blk = handles.pop().unwrap();
         let txn = handles.pop().unwrap();
         let blk = handles.pop().unwrap();
@@ -89,15 +139,46 @@ impl ChainDB {
 
         assert!(handles.is_empty());
 
+        // journals below the solid block are pruned, so there are only a few
+        let next_journal_seq = undo_journal
+            .new_iterator(ReadOptions::default_instance())
+            .keys()
+            .last()
+            .map(|key| BE::read_u64(key) + 1)
+            .unwrap_or(0);
+
+        let (events, _) = broadcast::channel(1_000);
+
         ChainDB {
             db: db,
             default: default,
             block_header: blk,
             transaction: txn,
             transaction_block: txn_blk,
+            transaction_receipt: txn_receipt,
+            contract: contract,
+            undo_journal,
+            next_journal_seq: AtomicU64::new(next_journal_seq),
+            witness_schedule: Mutex::new(None),
+            events,
         }
     }
 
+    /// Track solidity of new blocks with the active witnesses. Without a schedule, solidity is not tracked.
+    pub fn set_witness_schedule(&self, schedule: WitnessSchedule) {
+        *self.witness_schedule.lock().unwrap() = Some(schedule);
+    }
+
+    /// Subscribe to chain events. Slow receivers lag and miss events.
+    pub fn subscribe(&self) -> broadcast::Receiver<ChainEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, event: ChainEvent) {
+        // no subscriber is fine
+        let _ = self.events.send(event);
+    }
+
     pub fn get_node_id(&self) -> Vec<u8> {
         if let Ok(node_id) = self.default.get(ReadOptions::default_instance(), b"NODE_ID") {
             node_id.to_vec()
@@ -138,13 +219,47 @@ impl ChainDB {
             .map_err(From::from)
     }
 
+    /// Latest solid block number, `DynamicProperty::LatestSolidBlockNumber`.
+    pub fn get_solid_block_number(&self) -> i64 {
+        self.default
+            .get(ReadOptions::default_instance(), b"SOLID_BLOCK_NUMBER")
+            .map(|val| BE::read_u64(&*val) as i64)
+            .unwrap_or(0)
+    }
+
+    pub fn update_solid_block_number(&self, num: i64) -> Result<(), BoxError> {
+        let mut val = [0u8; 8];
+        BE::write_u64(&mut val, num as u64);
+        self.default
+            .put(WriteOptions::default_instance(), b"SOLID_BLOCK_NUMBER", &val)
+            .map_err(From::from)
+    }
+
+    /// Stats of witness block versions, encoded by `ForkController`.
+    pub fn get_fork_stats(&self) -> Vec<u8> {
+        self.default
+            .get(ReadOptions::default_instance(), b"FORK_STATS")
+            .map(|val| val.to_vec())
+            .unwrap_or_default()
+    }
+
     /// Highest block id, counted from 0
     pub fn highest_block(&self) -> Result<IndexedBlock, BoxError> {
         self.get_block_by_number(self.get_block_height() as u64)
     }
 
+    /// Head block header of the main chain. Before the main chain is tracked, the block at the highest number.
+    pub fn get_head_block_header(&self) -> Result<IndexedBlockHeader, BoxError> {
+        match self.get_head_hash(&StateOverlay::default())? {
+            Some(head_hash) => self.get_block_header_by_hash(&head_hash),
+            None => Ok(self.highest_block()?.header),
+        }
+    }
+
     pub fn insert_block(&self, block: &IndexedBlock) -> Result<(), Box<dyn Error>> {
+        let schedule = self.witness_schedule.lock().unwrap();
         let mut batch = WriteBatch::with_reserved_bytes(1024);
+        let mut overlay = StateOverlay::default();
 
         let mut buf = BytesMut::with_capacity(block.header.raw.encoded_len());
         block.header.raw.encode(&mut buf)?;
@@ -170,9 +285,47 @@ impl ChainDB {
                 &[txn.hash.as_bytes()],
                 &[block.hash().as_bytes(), &idx_key],
             );
+
+            // transaction_hash => TransactionReceipt
+            let receipt = receipt_from_block_transaction(block, txn);
+            buf.clear();
+            receipt.encode(&mut buf)?;
+            batch.put_cf(&self.transaction_receipt, txn.hash.as_bytes(), &buf);
         }
 
+        // State follows the main chain, blocks of a shorter fork are applied once the fork becomes the longest.
+        // Without a schedule, the main chain is not tracked, and state of every block is applied.
+        let head_hash = match *schedule {
+            Some(_) => self.get_head_hash(&overlay)?,
+            None => None,
+        };
+        let raw_header = block.header.raw.raw_data.as_ref().ok_or("malformed block header")?;
+        let (solid_block_number, reorg) = match head_hash {
+            Some(head_hash) if block.number() > 0 && head_hash.as_bytes() != &raw_header.parent_hash[..] => {
+                if block.number() > block_hash_to_number(head_hash.as_bytes()) {
+                    self.switch_chain_state(block, head_hash, schedule.as_ref(), &mut overlay, &mut batch)?
+                } else {
+                    debug!("block {} {:?} is on a fork", block.number(), block.hash());
+                    (None, None)
+                }
+            }
+            _ => (self.apply_block_state(block, schedule.as_ref(), &mut overlay, &mut batch)?, None),
+        };
+
         self.db.write(WriteOptions::default_instance(), &batch)?;
+        drop(schedule);
+        if let Some(solid_block_number) = solid_block_number {
+            debug!("solid block number => {}", solid_block_number);
+            self.prune_undo_journals(solid_block_number)?;
+        }
+        metrics::BLOCKS_INSERTED.inc();
+        metrics::TRANSACTIONS_INSERTED.inc_by(block.transactions.len() as _);
+        if self.events.receiver_count() > 0 {
+            self.publish(ChainEvent::NewBlock(Arc::new(block.clone())));
+        }
+        if let Some(reorg) = reorg {
+            self.publish(reorg);
+        }
         Ok(())
     }
 
@@ -272,6 +425,13 @@ impl ChainDB {
         self.get_block_from_header(header)
     }
 
+    pub fn get_block_header_by_hash(&self, hash: &H256) -> Result<IndexedBlockHeader, BoxError> {
+        let raw = self
+            .block_header
+            .get(ReadOptions::default_instance(), hash.as_bytes())?;
+        Ok(IndexedBlockHeader::new(*hash, BlockHeader::decode(&*raw)?))
+    }
+
     pub fn get_block_by_hash(&self, hash: &H256) -> Result<IndexedBlock, BoxError> {
         self.get_block_by_id(hash)
     }
@@ -303,6 +463,379 @@ impl ChainDB {
         Ok(txn)
     }
 
+    /// Block hash and index of a transaction in the block.
+    pub fn get_transaction_position(&self, id: &H256) -> Result<(H256, u64), BoxError> {
+        let block_key = self
+            .transaction_block
+            .get(ReadOptions::default_instance(), id.as_bytes())?;
+        Ok((H256::from_slice(&block_key[..32]), BE::read_u64(&block_key[32..])))
+    }
+
+    pub fn get_transaction_receipt(&self, id: &H256) -> Result<TransactionReceipt, BoxError> {
+        self.transaction_receipt
+            .get(ReadOptions::default_instance(), id.as_bytes())
+            .map_err(From::from)
+            .and_then(|raw| TransactionReceipt::decode(&*raw).map_err(From::from))
+    }
+
+    pub fn get_smart_contract(&self, address: &[u8]) -> Result<SmartContract, BoxError> {
+        self.contract
+            .get(ReadOptions::default_instance(), address)
+            .map_err(From::from)
+            .and_then(|raw| SmartContract::decode(&*raw).map_err(From::from))
+    }
+
+    fn state_column(&self, column: StateColumn) -> &ColumnFamily {
+        match column {
+            StateColumn::Contract => &self.contract,
+            StateColumn::Chain => &self.default,
+        }
+    }
+
+    /// Read a state key, pending changes of the overlay first.
+    fn get_state(&self, column: StateColumn, key: &[u8], overlay: &StateOverlay) -> Result<Option<Vec<u8>>, BoxError> {
+        if let Some(val) = overlay.get(column, key) {
+            return Ok(val.map(|val| val.to_vec()));
+        }
+        match self.state_column(column).get(ReadOptions::default_instance(), key) {
+            Ok(val) => Ok(Some(val.to_vec())),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write a state key, recording its previous value in the undo journal.
+    fn put_state(
+        &self,
+        column: StateColumn,
+        key: &[u8],
+        val: &[u8],
+        journal: &mut UndoJournal,
+        overlay: &mut StateOverlay,
+        wb: &mut WriteBatch,
+    ) -> Result<(), BoxError> {
+        if !journal.is_recorded(column, key) {
+            let prev = self.get_state(column, key, overlay)?;
+            journal.record(column, key, prev);
+        }
+        overlay.put(column, key, val);
+        wb.put_cf(self.state_column(column), key, val);
+        Ok(())
+    }
+
+    fn put_undo_journal(&self, journal: &UndoJournal, wb: &mut WriteBatch) {
+        // blocks without state changes have nothing to undo
+        if journal.is_empty() {
+            return;
+        }
+        let mut key = [0u8; 8];
+        BE::write_u64(&mut key, self.next_journal_seq.fetch_add(1, Ordering::SeqCst));
+        wb.put_cf(&self.undo_journal, &key, &journal.encode());
+    }
+
+    /// Roll back state changes of all blocks since block number `num`, the latest inserted first.
+    fn revert_state_since(&self, num: i64, overlay: &mut StateOverlay, wb: &mut WriteBatch) -> Result<(), BoxError> {
+        let mut journals = vec![];
+        for (key, raw) in self.undo_journal.new_iterator(ReadOptions::default_instance()) {
+            if UndoJournal::block_number_of(raw) >= num {
+                journals.push((key.to_vec(), UndoJournal::decode(raw)?));
+            }
+        }
+        for (journal_key, journal) in journals.iter().rev() {
+            for (column, key, prev) in journal.undo_entries() {
+                match prev {
+                    Some(val) => {
+                        overlay.put(column, key, val);
+                        wb.put_cf(self.state_column(column), key, val);
+                    }
+                    None => {
+                        overlay.delete(column, key);
+                        wb.delete_cf(self.state_column(column), key);
+                    }
+                }
+            }
+            wb.delete_cf(&self.undo_journal, journal_key);
+            info!("revert state of block {} {:?}", journal.block_number, journal.block_hash);
+        }
+        Ok(())
+    }
+
+    /// Apply state changes of a block, recording the undo journal. Returns the new solid block number if it advances.
+    fn apply_block_state(
+        &self,
+        block: &IndexedBlock,
+        schedule: Option<&WitnessSchedule>,
+        overlay: &mut StateOverlay,
+        wb: &mut WriteBatch,
+    ) -> Result<Option<i64>, BoxError> {
+        let mut journal = UndoJournal::new(block.number(), *block.hash());
+        for txn in &block.transactions {
+            if receipt_from_block_transaction(block, txn).success {
+                self.update_smart_contract(txn, &mut journal, overlay, wb)?;
+            }
+        }
+        let solid_block_number = match schedule {
+            Some(schedule) => self.update_witness_stats(block, schedule, &mut journal, overlay, wb)?,
+            None => None,
+        };
+        self.put_undo_journal(&journal, wb);
+        Ok(solid_block_number)
+    }
+
+    /// Hash of the main chain head block, tracked with solidity. `None` before solidity is tracked.
+    fn get_head_hash(&self, overlay: &StateOverlay) -> Result<Option<H256>, BoxError> {
+        let raw = self.get_state(StateColumn::Chain, b"SOLIDITY", overlay)?;
+        Ok(raw.and_then(|raw| SolidityTracker::head_hash_of(&raw)))
+    }
+
+    /// Headers of the main chain ending at `head_hash`, and of the fork ending at `header`, since their common
+    /// ancestor, in order of block number.
+    fn find_fork_branches(
+        &self,
+        header: &IndexedBlockHeader,
+        head_hash: H256,
+    ) -> Result<(Vec<IndexedBlockHeader>, Vec<IndexedBlockHeader>), BoxError> {
+        let parent_hash_of =
+            |header: &IndexedBlockHeader| H256::from_slice(&header.raw.raw_data.as_ref().unwrap().parent_hash);
+
+        let mut main_branch = vec![];
+        let mut fork_branch = vec![header.clone()];
+        let mut main_hash = head_hash;
+        let mut fork_hash = parent_hash_of(header);
+        // walk back the higher branch, until both reach the common ancestor
+        while main_hash != fork_hash {
+            if block_hash_to_number(fork_hash.as_bytes()) >= block_hash_to_number(main_hash.as_bytes()) {
+                let header = self.get_block_header_by_hash(&fork_hash)?;
+                fork_hash = parent_hash_of(&header);
+                fork_branch.push(header);
+            } else {
+                let header = self.get_block_header_by_hash(&main_hash)?;
+                main_hash = parent_hash_of(&header);
+                main_branch.push(header);
+            }
+        }
+        main_branch.reverse();
+        fork_branch.reverse();
+        Ok((main_branch, fork_branch))
+    }
+
+    /// Switch state to the fork of a new block, which is longer than the main chain. State of the main chain is
+    /// rolled back to the common ancestor, then blocks of the fork are applied, in the same write batch as the new
+    /// block. Returns the new solid block number if it advances, and the reorg event unless the switch is refused.
+    fn switch_chain_state(
+        &self,
+        block: &IndexedBlock,
+        head_hash: H256,
+        schedule: Option<&WitnessSchedule>,
+        overlay: &mut StateOverlay,
+        wb: &mut WriteBatch,
+    ) -> Result<(Option<i64>, Option<ChainEvent>), BoxError> {
+        let (main_branch, fork_branch) = self.find_fork_branches(&block.header, head_hash)?;
+        let fork_at = fork_branch[0].number();
+        // undo journals of solid blocks are pruned
+        if fork_at <= self.get_solid_block_number() {
+            warn!("block {} {:?} forks at solid block {}, ignored", block.number(), block.hash(), fork_at);
+            return Ok((None, None));
+        }
+        info!(
+            "switch main chain at block {}, {} blocks reverted, {} blocks applied",
+            fork_at,
+            main_branch.len(),
+            fork_branch.len()
+        );
+
+        self.revert_state_since(fork_at, overlay, wb)?;
+        let mut solid_block_number = None;
+        let mut fork_txns: HashSet<H256> = block.transactions.iter().map(|txn| txn.hash).collect();
+        for header in &fork_branch[..fork_branch.len() - 1] {
+            let fork_block = self.get_block_from_header(header.clone())?;
+            solid_block_number = self
+                .apply_block_state(&fork_block, schedule, overlay, wb)?
+                .or(solid_block_number);
+            fork_txns.extend(fork_block.transactions.iter().map(|txn| txn.hash));
+        }
+        solid_block_number = self
+            .apply_block_state(block, schedule, overlay, wb)?
+            .or(solid_block_number);
+
+        let mut orphan_transactions = vec![];
+        for header in &main_branch {
+            let main_block = self.get_block_from_header(header.clone())?;
+            orphan_transactions.extend(
+                main_block
+                    .transactions
+                    .iter()
+                    .map(|txn| txn.hash)
+                    .filter(|hash| !fork_txns.contains(hash)),
+            );
+        }
+        let reorg = ChainEvent::Reorg {
+            fork_at: fork_at as u64,
+            purged_blocks: main_branch.iter().map(|header| header.hash).collect(),
+            new_blocks: fork_branch.iter().map(|header| header.hash).collect(),
+            orphan_transactions,
+        };
+        Ok((solid_block_number, Some(reorg)))
+    }
+
+    /// Re-apply state changes of a stored block, when replaying into a state db.
+    pub fn replay_block(&self, block: &IndexedBlock) -> Result<(), BoxError> {
+        let schedule = self.witness_schedule.lock().unwrap();
+        let mut wb = WriteBatch::with_reserved_bytes(1024);
+        self.apply_block_state(block, schedule.as_ref(), &mut StateOverlay::default(), &mut wb)?;
+        self.db.write(WriteOptions::default_instance(), &wb).map_err(From::from)
+    }
+
+    /// Track a main chain block of active witnesses, for solidity and block versions. Returns the new solid block
+    /// number if it advances.
+    ///
+    /// Solidity state, the solid block number and fork stats are journaled, so they are rolled back on chain fork.
+    fn update_witness_stats(
+        &self,
+        block: &IndexedBlock,
+        schedule: &WitnessSchedule,
+        journal: &mut UndoJournal,
+        overlay: &mut StateOverlay,
+        wb: &mut WriteBatch,
+    ) -> Result<Option<i64>, BoxError> {
+        let witnesses = schedule.witnesses();
+        let mut tracker = match self.get_state(StateColumn::Chain, b"SOLIDITY", overlay)? {
+            Some(raw) => SolidityTracker::decode(&raw, witnesses.len()),
+            None => SolidityTracker::new(witnesses.len()),
+        };
+        let raw_header = block.header.raw.raw_data.as_ref().ok_or("malformed block header")?;
+        if !tracker.is_main_chain(block.number(), &raw_header.parent_hash) {
+            return Ok(None);
+        }
+
+        let parent_timestamp = if block.number() == 0 {
+            raw_header.timestamp
+        } else {
+            self.get_block_header_by_hash(&H256::from_slice(&raw_header.parent_hash))?
+                .timestamp()
+        };
+        let mut fork = ForkController::new(schedule.maintenance_interval());
+        fork.decode_stats(
+            &self
+                .get_state(StateColumn::Chain, b"FORK_STATS", overlay)?
+                .unwrap_or_default(),
+        )?;
+        fork.update(witnesses, block.witness(), raw_header.version, block.number(), raw_header.timestamp);
+        if schedule.is_maintenance_block(parent_timestamp, raw_header.timestamp) {
+            fork.reset();
+        }
+        self.put_state(StateColumn::Chain, b"FORK_STATS", &fork.encode_stats(), journal, overlay, wb)?;
+
+        let solid_block_number = tracker.apply_block(witnesses, block.witness(), block.number(), *block.hash());
+        self.put_state(StateColumn::Chain, b"SOLIDITY", &tracker.encode(), journal, overlay, wb)?;
+        let prev_solid_block_number = self
+            .get_state(StateColumn::Chain, b"SOLID_BLOCK_NUMBER", overlay)?
+            .map(|val| BE::read_u64(&val) as i64)
+            .unwrap_or(0);
+        if solid_block_number <= prev_solid_block_number {
+            return Ok(None);
+        }
+        let mut val = [0u8; 8];
+        BE::write_u64(&mut val, solid_block_number as u64);
+        self.put_state(StateColumn::Chain, b"SOLID_BLOCK_NUMBER", &val, journal, overlay, wb)?;
+        Ok(Some(solid_block_number))
+    }
+
+    /// SHA256 of all state, in key order of each state column. Specific to OpenTron's db layout, not comparable with
+    /// java-tron.
+    pub fn state_checksum(&self) -> H256 {
+        let mut hasher = Sha256::new();
+        for &column in &[StateColumn::Contract] {
+            hasher.update(&[column as u8]);
+            for (key, val) in self.state_column(column).new_iterator(ReadOptions::default_instance()) {
+                let mut len = [0u8; 8];
+                BE::write_u32(&mut len[..4], key.len() as u32);
+                BE::write_u32(&mut len[4..], val.len() as u32);
+                hasher.update(&len);
+                hasher.update(key);
+                hasher.update(val);
+            }
+        }
+        H256::from_slice(&hasher.finalize())
+    }
+
+    /// Delete undo journals of blocks below the solid block, which can never be forked.
+    pub fn prune_undo_journals(&self, solid_block_number: i64) -> Result<(), BoxError> {
+        let mut wb = WriteBatch::with_reserved_bytes(1024);
+        self.undo_journal
+            .new_iterator(ReadOptions::default_instance())
+            .filter(|(_, raw)| UndoJournal::block_number_of(raw) < solid_block_number)
+            .for_each(|(key, _)| wb.delete_cf(&self.undo_journal, key));
+        self.db.write(WriteOptions::default_instance(), &wb).map_err(From::from)
+    }
+
+    /// Re-track smart contracts of all stored blocks, for dbs synced before smart contracts were tracked.
+    ///
+    /// Run on a stopped node. Changes are not journaled, so blocks after the solid block can not be reverted.
+    pub fn backfill_smart_contracts(&self) -> Result<(), BoxError> {
+        let height = self.get_block_height() as u64;
+        for num in 1..=height {
+            let block = self.get_block_by_number(num)?;
+            let mut journal = UndoJournal::new(block.number(), *block.hash());
+            let mut overlay = StateOverlay::default();
+            let mut wb = WriteBatch::with_reserved_bytes(1024);
+            for txn in &block.transactions {
+                if receipt_from_block_transaction(&block, txn).success {
+                    self.update_smart_contract(txn, &mut journal, &mut overlay, &mut wb)?;
+                }
+            }
+            self.db.write(WriteOptions::default_instance(), &wb)?;
+            if num % 100_000 == 0 {
+                info!("backfilled smart contracts to block {}", num);
+            }
+        }
+        Ok(())
+    }
+
+    /// Track smart contracts(and their ABIs) created or changed by a transaction. Malformed transactions are
+    /// skipped, they are accepted by the chain anyway.
+    fn update_smart_contract(
+        &self,
+        txn: &IndexedTransaction,
+        journal: &mut UndoJournal,
+        overlay: &mut StateOverlay,
+        wb: &mut WriteBatch,
+    ) -> Result<(), BoxError> {
+        let contract = txn.raw.raw_data.as_ref().and_then(|raw| raw.contract.as_ref());
+        match contract.and_then(|cntr| ContractType::from_i32(cntr.r#type)) {
+            Some(ContractType::CreateSmartContract) => {
+                if let Some(smart_cntr) = created_smart_contract(txn) {
+                    let mut buf = Vec::with_capacity(smart_cntr.encoded_len());
+                    smart_cntr.encode(&mut buf)?;
+                    self.put_state(StateColumn::Contract, &smart_cntr.contract_address, &buf, journal, overlay, wb)?;
+                } else {
+                    warn!("malformed CreateSmartContract in transaction {:?}, skipped", txn.hash);
+                }
+            }
+            Some(ContractType::ClearAbiContract) => {
+                let cntr = match contract
+                    .and_then(|cntr| cntr.parameter.as_ref())
+                    .map(|param| ClearAbiContract::decode(&param.value[..]))
+                {
+                    Some(Ok(cntr)) => cntr,
+                    _ => {
+                        warn!("malformed ClearAbiContract in transaction {:?}, skipped", txn.hash);
+                        return Ok(());
+                    }
+                };
+                let raw = self.get_state(StateColumn::Contract, &cntr.contract_address, overlay)?;
+                if let Some(Ok(mut smart_cntr)) = raw.map(|raw| SmartContract::decode(&raw[..])) {
+                    smart_cntr.abi = None;
+                    let mut buf = Vec::with_capacity(smart_cntr.encoded_len());
+                    smart_cntr.encode(&mut buf)?;
+                    self.put_state(StateColumn::Contract, &smart_cntr.contract_address, &buf, journal, overlay, wb)?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
     pub fn get_block_header_by_transaction(&self, txn: &IndexedTransaction) -> Result<IndexedBlockHeader, BoxError> {
         let block_key = self
             .transaction_block
@@ -323,6 +856,7 @@ impl ChainDB {
             if e.is_not_found() {
                 wb.deletev_cf(&self.transaction, &[&*block_key, txn.hash.as_bytes()]);
                 wb.delete_cf(&self.transaction_block, txn.hash.as_bytes());
+                wb.delete_cf(&self.transaction_receipt, txn.hash.as_bytes());
                 return Ok(());
             }
         }
@@ -355,6 +889,7 @@ impl ChainDB {
                 info!("delete transaction {}", hex::encode(&key[32 + 8..]));
                 wb.delete_cf(&self.transaction, key);
                 wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
+                wb.delete_cf(&self.transaction_receipt, &key[32 + 8..]);
             });
 
         self.db.write(WriteOptions::default_instance(), &wb)?;
@@ -375,6 +910,7 @@ impl ChainDB {
             .for_each(|key| {
                 wb.delete_cf(&self.transaction, &key);
                 wb.delete_cf(&self.transaction_block, &key[32 + 8..]);
+                wb.delete_cf(&self.transaction_receipt, &key[32 + 8..]);
             });
 
         self.db.write(WriteOptions::default_instance(), &wb).is_ok()
@@ -416,6 +952,11 @@ impl ChainDB {
                     hex::encode(&corrent_reverse_index),
                 );
                 wb.put_cf(&self.transaction_block, txn.hash.as_ref(), &corrent_reverse_index);
+                // receipt carries block number and timestamp, rebuild it for the right block
+                let receipt = receipt_from_block_transaction(block, txn);
+                let mut buf = Vec::with_capacity(receipt.encoded_len());
+                receipt.encode(&mut buf).unwrap();
+                wb.put_cf(&self.transaction_receipt, txn.hash.as_ref(), &buf);
             }
         });
     }
@@ -434,6 +975,7 @@ impl ChainDB {
         assert!(num > 0, "cannot fork from genesis block");
         assert!(self.get_block_headers_by_number(num - 1).len() == 1);
         assert!(self.get_block_headers_by_number(num).len() > 1);
+        let fork_at = num;
 
         let mut forks: Vec<LinkedList<IndexedBlockHeader>> = Vec::new();
         loop {
@@ -487,7 +1029,7 @@ impl ChainDB {
             }
         }
 
-        for fork in tobe_purged_forks {
+        for fork in &tobe_purged_forks {
             for header in fork.iter() {
                 // wb.delete_cf(&self.block_header, header.hash.as_bytes());
                 let block = self.get_block_from_header(header.clone()).unwrap();
@@ -504,7 +1046,33 @@ impl ChainDB {
         if dry_run {
             return Ok(());
         }
+        // undo journals of solid blocks are pruned
+        if fork_at as i64 <= self.get_solid_block_number() {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "can not fork at a solid block",
+            )));
+        }
+        // roll back state of all forks, including solidity, then re-apply the longest fork, in one batch
+        let schedule = self.witness_schedule.lock().unwrap();
+        let mut overlay = StateOverlay::default();
+        self.revert_state_since(fork_at as i64, &mut overlay, &mut wb)?;
+        for header in longest_fork.iter() {
+            let block = self.get_block_from_header(header.clone())?;
+            self.apply_block_state(&block, schedule.as_ref(), &mut overlay, &mut wb)?;
+        }
         self.db.write(WriteOptions::default_instance(), &wb)?;
+        drop(schedule);
+
+        self.publish(ChainEvent::Reorg {
+            fork_at,
+            purged_blocks: tobe_purged_forks
+                .iter()
+                .flat_map(|fork| fork.iter().map(|header| header.hash))
+                .collect(),
+            new_blocks: longest_fork.iter().map(|header| header.hash).collect(),
+            orphan_transactions: orphan_txns.iter().map(|txn| txn.hash).collect(),
+        });
 
         if !orphan_txns.is_empty() {
             let mut f = OpenOptions::new()
@@ -546,6 +1114,38 @@ impl ChainDB {
             })
     }
 
+    /// Block headers from key `start` to block number `to_block`, inclusive. Forks are included.
+    pub fn get_block_headers_in_range(&self, start: &[u8], to_block: u64, limit: usize) -> Vec<IndexedBlockHeader> {
+        let mut upper_bound = [0u8; 8];
+        BE::write_u64(&mut upper_bound[..], to_block + 1);
+
+        self.block_header
+            .new_iterator(
+                &ReadOptions::default()
+                    .iterate_lower_bound(start)
+                    .iterate_upper_bound(&upper_bound[..]),
+            )
+            .take(limit)
+            .map(|(key, val)| IndexedBlockHeader::new(H256::from_slice(key), BlockHeader::decode(val).unwrap()))
+            .collect()
+    }
+
+    /// Number of blocks in the range, including blocks of forks.
+    pub fn count_blocks_in_range(&self, from_block: u64, to_block: u64) -> usize {
+        let mut lower_bound = [0u8; 8];
+        BE::write_u64(&mut lower_bound[..], from_block);
+        let mut upper_bound = [0u8; 8];
+        BE::write_u64(&mut upper_bound[..], to_block + 1);
+
+        self.block_header
+            .new_iterator(
+                &ReadOptions::default()
+                    .iterate_lower_bound(&lower_bound[..])
+                    .iterate_upper_bound(&upper_bound[..]),
+            )
+            .count()
+    }
+
     pub fn blocks<'a>(&'a self) -> impl Iterator<Item = IndexedBlock> + 'a {
         self.block_header
             .new_iterator(ReadOptions::default_instance())
@@ -674,6 +1274,9 @@ impl ChainDB {
             &self.block_header,
             &self.transaction,
             &self.transaction_block,
+            &self.transaction_receipt,
+            &self.contract,
+            &self.undo_journal,
         ]
         .iter()
         .map(|cf| cf.get_int_property(key).unwrap_or_default())
@@ -721,6 +1324,22 @@ impl ChainDB {
         self.block_header.compact_range(&Default::default(), ..)?;
         self.transaction.compact_range(&Default::default(), ..)?;
         self.transaction_block.compact_range(&Default::default(), ..)?;
+        self.transaction_receipt.compact_range(&Default::default(), ..)?;
+        self.contract.compact_range(&Default::default(), ..)?;
+        self.undo_journal.compact_range(&Default::default(), ..)?;
+        Ok(())
+    }
+
+    /// Create a consistent checkpoint of the live db, SST files are hard-linked. The checkpoint dir must not exist.
+    pub fn create_checkpoint<P: AsRef<Path>>(&self, checkpoint_dir: P) -> Result<(), BoxError> {
+        if checkpoint_dir.as_ref().exists() {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "checkpoint dir already exists",
+            )));
+        }
+        let checkpoint = Checkpoint::new(&self.db)?;
+        checkpoint.create_checkpoint(checkpoint_dir)?;
         Ok(())
     }
 
@@ -18,6 +18,7 @@ use tokio::pin;
 
 use super::protocol::{DiscoveryMessage, DiscoveryMessageTransport};
 use crate::context::AppContext;
+use crate::metrics;
 use crate::util::Peer;
 
 fn common_prefix_bits(a: &[u8], b: &[u8]) -> u32 {
@@ -52,6 +53,7 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
 
     let peers_data = std::fs::read_to_string("./peers.json").unwrap_or("[]".to_string());
     let mut peers_db: HashSet<Peer> = serde_json::from_str(&peers_data)?;
+    metrics::DISCOVERY_PEERS.set(peers_db.len() as _);
 
     let my_endpoint = channel_config
         .advertised_endpoint
@@ -193,6 +195,7 @@ pub async fn discovery_server(ctx: Arc<AppContext>, signal: broadcast::Receiver<
                         };
                         if !peers_db.contains(&peer) {
                             peers_db.insert(peer);
+                            metrics::DISCOVERY_PEERS.set(peers_db.len() as _);
                             std::fs::write("./peers.json", serde_json::to_string_pretty(&peers_db)?.as_bytes())?;
                         }
                     }
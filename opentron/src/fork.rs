@@ -0,0 +1,307 @@
+//! Hard fork activation by block versions, same as java-tron's `ForkController`.
+//!
+//! Witnesses announce their versions in the blocks they produce. A version passes once all active witnesses have
+//! produced blocks of the version since the last maintenance, and a passed version is never downgraded. Passing a
+//! version passes all lower versions.
+//!
+//! Stats are updated by `ChainDB::insert_block` with main chain blocks, in the same write batch as the block, and
+//! rolled back on chain fork.
+//!
+//! The block producer loads the controller at the main chain head, and packs only transactions of active features.
+//!
+//! NOTE: Votes are not counted, so the active witnesses are always the genesis witnesses. On networks where the
+//! elected witnesses differ from the genesis ones, like mainnet, stats are meaningless, so loading fails once the head
+//! block is produced by a witness out of the genesis schedule.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::constants::BlockVersion;
+use crate::db::ChainDB;
+use crate::producer::WitnessSchedule;
+use crate::state::ChainParameter;
+
+/// `Odyssey3_2` is activated by block number, instead of witness versions.
+///
+/// Renamed: `ENERGY_LIMIT_BLOCK_NUM`
+pub const ENERGY_LIMIT_BLOCK_NUMBER: i64 = 4727890;
+
+/// Versions tracked by the fork controller, in ascending order.
+const FORK_VERSIONS: &[BlockVersion] = &[
+    BlockVersion::Odyssey3_2,
+    BlockVersion::Odyssey3_2_2,
+    BlockVersion::Odyssey3_5,
+    BlockVersion::Odyssey3_6_0,
+    BlockVersion::Odyssey3_6_5,
+    BlockVersion::Odyssey3_6_6,
+    BlockVersion::GreatVoyage4_0_0,
+    BlockVersion::GreatVoyage4_0_1,
+];
+
+/// Hard fork time and min number of upgraded witnesses, of versions forked by timestamp.
+fn hard_fork_of(version: BlockVersion) -> Option<(i64, usize)> {
+    match version {
+        BlockVersion::GreatVoyage4_0_1 => Some((1_596_780_000_000, 22)),
+        _ => None,
+    }
+}
+
+/// The version a chain parameter requires, proposals of the parameter are invalid before the version passes.
+fn required_version_of(param: ChainParameter) -> Option<BlockVersion> {
+    use ChainParameter::*;
+
+    match param {
+        TotalEnergyLimit => Some(BlockVersion::Odyssey3_2),
+        TotalEnergyCurrentLimit => Some(BlockVersion::Odyssey3_2_2),
+        AllowMultisig | AllowAdaptiveEnergy | AccountPermissionUpdateFee | MultisigFee => {
+            Some(BlockVersion::Odyssey3_5)
+        }
+        AllowProtoFilterNum | AllowAccountStateRoot | AllowTvmConstantinopleUpgrade => Some(BlockVersion::Odyssey3_6_0),
+        AllowTvmSolidity059Upgrade |
+        AdaptiveResourceLimitTargetRatio |
+        AdaptiveResourceLimitMultiplier |
+        AllowChangeDelegation |
+        StandbyWitnessPayPerBlock => Some(BlockVersion::Odyssey3_6_5),
+        ForbidTransferToContract => Some(BlockVersion::Odyssey3_6_6),
+        AllowTvmShieldedUpgrade => Some(BlockVersion::GreatVoyage4_0_0),
+        _ => None,
+    }
+}
+
+fn is_all_upgraded(stats: Option<&Vec<bool>>) -> bool {
+    stats.map(|stats| !stats.is_empty() && stats.iter().all(|&upgraded| upgraded)) == Some(true)
+}
+
+pub struct ForkController {
+    maintenance_interval: i64,
+    latest_block_number: i64,
+    latest_block_timestamp: i64,
+    /// Block version => whether the witness of each active witness slot has upgraded.
+    stats: BTreeMap<i32, Vec<bool>>,
+}
+
+impl ForkController {
+    pub fn new(maintenance_interval: i64) -> Self {
+        ForkController {
+            maintenance_interval,
+            latest_block_number: 0,
+            latest_block_timestamp: 0,
+            stats: BTreeMap::new(),
+        }
+    }
+
+    /// Fork controller at the main chain head of the chain db, with saved stats. Fails if the active witnesses are
+    /// unknown, that is the head block is not produced by a witness of the schedule.
+    pub fn load(db: &ChainDB, schedule: &WitnessSchedule) -> Result<Self, Box<dyn Error>> {
+        let head = db.get_head_block_header()?;
+        let raw_header = head.raw.raw_data.as_ref().ok_or("malformed block header")?;
+        let witness = &raw_header.witness_address;
+        let is_active_witness = schedule
+            .witnesses()
+            .iter()
+            .any(|wit| wit.as_bytes() == witness.as_slice());
+        if head.number() > 0 && !is_active_witness {
+            return Err(format!(
+                "active witnesses unknown, head block {} is produced by {}, out of the genesis witnesses",
+                head.number(),
+                hex::encode(witness)
+            )
+            .into());
+        }
+
+        let mut fork = Self::new(schedule.maintenance_interval());
+        fork.decode_stats(&db.get_fork_stats())?;
+        fork.set_head(head.number(), head.timestamp());
+        Ok(fork)
+    }
+
+    pub fn set_head(&mut self, block_number: i64, timestamp: i64) {
+        self.latest_block_number = block_number;
+        self.latest_block_timestamp = timestamp;
+    }
+
+    /// Is the block version activated.
+    pub fn pass(&self, version: BlockVersion) -> bool {
+        if version < BlockVersion::Odyssey3_2 {
+            return true;
+        }
+        if version == BlockVersion::Odyssey3_2 {
+            return self.latest_block_number >= ENERGY_LIMIT_BLOCK_NUMBER;
+        }
+        let stats = self.stats.get(&(version as i32));
+        match hard_fork_of(version) {
+            Some((fork_time, min_num_upgraded)) => {
+                // aligned to maintenance
+                let fork_time = ((fork_time - 1) / self.maintenance_interval + 1) * self.maintenance_interval;
+                let num_upgraded = stats.map(|stats| stats.iter().filter(|&&upgraded| upgraded).count());
+                self.latest_block_timestamp >= fork_time && num_upgraded.unwrap_or(0) >= min_num_upgraded
+            }
+            None => is_all_upgraded(stats),
+        }
+    }
+
+    /// Is the feature of a chain parameter activated.
+    pub fn is_active(&self, feature: ChainParameter) -> bool {
+        required_version_of(feature)
+            .map(|version| self.pass(version))
+            .unwrap_or(true)
+    }
+
+    /// Update stats with a new block of the witness, `witnesses` are active witnesses.
+    pub fn update<T: AsRef<[u8]>>(
+        &mut self,
+        witnesses: &[T],
+        witness: &[u8],
+        version: i32,
+        block_number: i64,
+        timestamp: i64,
+    ) {
+        self.set_head(block_number, timestamp);
+
+        let slot = match witnesses.iter().position(|wit| wit.as_ref() == witness) {
+            Some(slot) => slot,
+            None => return,
+        };
+        if version < BlockVersion::Odyssey3_2 as i32 {
+            return;
+        }
+
+        // the witness is no longer at higher versions
+        for (_, stats) in self.stats.range_mut(version + 1..) {
+            if !is_all_upgraded(Some(stats)) {
+                if let Some(upgraded) = stats.get_mut(slot) {
+                    *upgraded = false;
+                }
+            }
+        }
+
+        let stats = self.stats.entry(version).or_default();
+        if stats.len() != witnesses.len() {
+            *stats = vec![false; witnesses.len()];
+        }
+        if is_all_upgraded(Some(stats)) {
+            for lower_version in FORK_VERSIONS.iter().map(|&ver| ver as i32).filter(|&ver| ver < version) {
+                let stats = self.stats.entry(lower_version).or_default();
+                if !is_all_upgraded(Some(stats)) {
+                    *stats = vec![true; witnesses.len()];
+                }
+            }
+            return;
+        }
+        stats[slot] = true;
+    }
+
+    /// Clear stats of versions not passed yet, on maintenance.
+    pub fn reset(&mut self) {
+        for stats in self.stats.values_mut() {
+            if !is_all_upgraded(Some(stats)) {
+                stats.iter_mut().for_each(|upgraded| *upgraded = false);
+            }
+        }
+    }
+
+    /// [version: u8, num_witnesses: u8, [upgraded: u8]*]*
+    pub fn encode_stats(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        for (&version, stats) in &self.stats {
+            buf.push(version as u8);
+            buf.push(stats.len() as u8);
+            buf.extend(stats.iter().map(|&upgraded| upgraded as u8));
+        }
+        buf
+    }
+
+    pub fn decode_stats(&mut self, mut raw: &[u8]) -> Result<(), Box<dyn Error>> {
+        while raw.len() >= 2 {
+            let (version, len) = (raw[0] as i32, raw[1] as usize);
+            if raw.len() < 2 + len {
+                return Err("malformed fork stats".into());
+            }
+            self.stats
+                .insert(version, raw[2..2 + len].iter().map(|&upgraded| upgraded != 0).collect());
+            raw = &raw[2 + len..];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAINTENANCE_INTERVAL: i64 = 6 * 3600 * 1000;
+
+    fn witnesses(n: u8) -> Vec<Vec<u8>> {
+        (0..n).map(|i| vec![i; 21]).collect()
+    }
+
+    #[test]
+    fn test_energy_limit_by_block_number() {
+        let mut fork = ForkController::new(MAINTENANCE_INTERVAL);
+        assert!(fork.pass(BlockVersion::Odyssey3_1_0));
+        assert!(!fork.pass(BlockVersion::Odyssey3_2));
+        assert!(!fork.is_active(ChainParameter::TotalEnergyLimit));
+        fork.set_head(ENERGY_LIMIT_BLOCK_NUMBER, 0);
+        assert!(fork.pass(BlockVersion::Odyssey3_2));
+        assert!(fork.is_active(ChainParameter::TotalEnergyLimit));
+        assert!(fork.is_active(ChainParameter::EnergyFee));
+    }
+
+    #[test]
+    fn test_pass_by_all_witnesses() {
+        let wits = witnesses(3);
+        let version = BlockVersion::Odyssey3_5 as i32;
+        let mut fork = ForkController::new(MAINTENANCE_INTERVAL);
+        fork.update(&wits, &wits[0], version, 1, 0);
+        fork.update(&wits, &wits[1], version, 2, 0);
+        // unknown witness
+        fork.update(&wits, &[9; 21], version, 3, 0);
+        // downgraded
+        fork.update(&wits, &wits[0], BlockVersion::Odyssey3_2_2 as i32, 4, 0);
+        fork.update(&wits, &wits[2], version, 5, 0);
+        assert!(!fork.pass(BlockVersion::Odyssey3_5));
+
+        fork.update(&wits, &wits[0], version, 6, 0);
+        assert!(fork.pass(BlockVersion::Odyssey3_5));
+        assert!(fork.is_active(ChainParameter::AllowMultisig));
+        assert!(!fork.pass(BlockVersion::Odyssey3_2_2));
+
+        // lower versions pass on the next block of the version
+        fork.update(&wits, &wits[1], version, 7, 0);
+        assert!(fork.pass(BlockVersion::Odyssey3_2_2));
+        assert!(!fork.pass(BlockVersion::Odyssey3_6_0));
+
+        let mut loaded = ForkController::new(MAINTENANCE_INTERVAL);
+        loaded.decode_stats(&fork.encode_stats()).unwrap();
+        assert_eq!(loaded.stats, fork.stats);
+    }
+
+    #[test]
+    fn test_reset_on_maintenance() {
+        let wits = witnesses(2);
+        let mut fork = ForkController::new(MAINTENANCE_INTERVAL);
+        fork.update(&wits, &wits[0], BlockVersion::Odyssey3_5 as i32, 1, 0);
+        fork.update(&wits, &wits[1], BlockVersion::Odyssey3_5 as i32, 2, 0);
+        fork.update(&wits, &wits[0], BlockVersion::Odyssey3_6_0 as i32, 3, 0);
+        // passed versions are kept
+        fork.reset();
+        fork.update(&wits, &wits[1], BlockVersion::Odyssey3_6_0 as i32, 4, 0);
+        assert!(fork.pass(BlockVersion::Odyssey3_5));
+        assert!(!fork.pass(BlockVersion::Odyssey3_6_0));
+    }
+
+    #[test]
+    fn test_hard_fork_by_timestamp() {
+        let wits = witnesses(27);
+        let version = BlockVersion::GreatVoyage4_0_1 as i32;
+        let fork_time = 1_596_780_000_000;
+        let mut fork = ForkController::new(MAINTENANCE_INTERVAL);
+        for (i, wit) in wits.iter().take(22).enumerate() {
+            fork.update(&wits, wit, version, i as i64, fork_time - 1_000);
+        }
+        assert!(!fork.pass(BlockVersion::GreatVoyage4_0_1));
+        fork.set_head(100, fork_time);
+        assert!(fork.pass(BlockVersion::GreatVoyage4_0_1));
+        assert!(!fork.pass(BlockVersion::GreatVoyage4_0_0));
+    }
+}
@@ -70,6 +70,18 @@ pub struct GenesisConfig {
 }
 
 impl GenesisConfig {
+    /// A new genesis, with the same parent hash, mantra and creator as mainnet.
+    pub fn new(timestamp: i64, witnesses: Vec<Witness>, allocs: Vec<Alloc>) -> Self {
+        GenesisConfig {
+            timestamp,
+            parent_hash: "0xe58f33f9baf9305dc6f82b9f1934ea8f0ade2defb951258d50167028c780351f".into(),
+            mantra: "A new system must allow existing systems to be linked together without requiring any central control or coordination".into(),
+            creator: "7YxAaK71utTpYJ8u4Zna7muWxd1pQwimpGxy8".into(),
+            witnesses,
+            allocs,
+        }
+    }
+
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
         let content = fs::read_to_string(path)?;
         Ok(serde_json::from_str(&content)?)
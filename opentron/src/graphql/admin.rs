@@ -0,0 +1,166 @@
+//! Admin API of node control, served at `/admin`, authenticated by a bearer token.
+
+use chrono::{DateTime, TimeZone, Utc};
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Request};
+use juniper::{EmptySubscription, FieldResult};
+use log::{info, warn};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use super::model::Context;
+use crate::channel::peer::PeerInfo;
+
+#[derive(juniper::GraphQLObject)]
+/// A connected channel peer.
+pub struct Peer {
+    address: String,
+    /// Is connected by the remote peer.
+    inbound: bool,
+    connected_at: DateTime<Utc>,
+    /// Highest block number known to the peer.
+    head_block_number: i32,
+    /// Round trip time of the latest ping, in ms.
+    latency: Option<i32>,
+    /// Is syncing blocks from the peer.
+    syncing: bool,
+}
+
+impl From<PeerInfo> for Peer {
+    fn from(info: PeerInfo) -> Self {
+        Peer {
+            address: info.remote_addr.to_string(),
+            inbound: info.inbound,
+            connected_at: Utc.timestamp(info.connected_at / 1_000, 0),
+            head_block_number: info.head_block_number as _,
+            latency: info.latency.map(|latency| latency as _),
+            syncing: info.syncing,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Sync state of the node.
+pub struct SyncState {
+    /// Is syncing blocks from any peer.
+    syncing: bool,
+    head_block_number: i32,
+    /// Highest block number known to connected peers.
+    highest_peer_block_number: Option<i32>,
+    num_peers: i32,
+    num_active_connections: i32,
+}
+
+pub(crate) struct AdminQuery;
+
+#[juniper::graphql_object(Context = Context)]
+impl AdminQuery {
+    /// Connected peers
+    fn peers(ctx: &Context) -> Vec<Peer> {
+        ctx.app.peers.list().into_iter().map(Peer::from).collect()
+    }
+
+    /// Banned IP addresses
+    fn banned_peers(ctx: &Context) -> Vec<String> {
+        ctx.app.peers.banned().iter().map(|ip| ip.to_string()).collect()
+    }
+
+    /// Static peers added at runtime
+    fn static_peers(ctx: &Context) -> Vec<String> {
+        ctx.app.peers.static_nodes()
+    }
+
+    /// Sync state
+    fn sync_state(ctx: &Context) -> SyncState {
+        let peers = ctx.app.peers.list();
+        SyncState {
+            syncing: peers.iter().any(|info| info.syncing),
+            head_block_number: ctx.app.db.get_block_height() as _,
+            highest_peer_block_number: peers.iter().map(|info| info.head_block_number as _).max(),
+            num_peers: peers.len() as _,
+            num_active_connections: ctx.app.num_active_connections.load(Ordering::SeqCst) as _,
+        }
+    }
+}
+
+pub(crate) struct AdminMutation;
+
+#[juniper::graphql_object(Context = Context)]
+impl AdminMutation {
+    /// Disconnect a peer, returns false if the peer is not connected
+    #[graphql(arguments(address(description = "peer address, like `1.2.3.4:18888`")))]
+    fn disconnect_peer(ctx: &Context, address: String) -> FieldResult<bool> {
+        let addr = address.parse::<SocketAddr>()?;
+        Ok(ctx.app.peers.disconnect(&addr))
+    }
+
+    /// Ban an IP address, connected peers from the address are disconnected
+    fn ban_peer(ctx: &Context, ip: String) -> FieldResult<bool> {
+        let ip = ip.parse::<IpAddr>()?;
+        warn!("ban peer {}", ip);
+        ctx.app.peers.ban(ip);
+        Ok(true)
+    }
+
+    /// Unban an IP address, returns false if the address is not banned
+    fn unban_peer(ctx: &Context, ip: String) -> FieldResult<bool> {
+        let ip = ip.parse::<IpAddr>()?;
+        Ok(ctx.app.peers.unban(&ip))
+    }
+
+    /// Add a static peer, connected by the active channel service
+    #[graphql(arguments(address(description = "peer address, like `1.2.3.4:18888`")))]
+    fn add_peer(ctx: &Context, address: String) -> FieldResult<bool> {
+        let addr = address.parse::<SocketAddr>()?;
+        Ok(ctx.app.peers.add_static_node(addr.to_string()))
+    }
+
+    /// Create a checkpoint of the database in background, a consistent copy with SST files hard-linked
+    #[graphql(arguments(path(description = "checkpoint dir, must not exist, and on the same filesystem")))]
+    fn create_checkpoint(ctx: &Context, path: String) -> FieldResult<bool> {
+        if Path::new(&path).exists() {
+            return Err(format!("checkpoint dir {} already exists", path).into());
+        }
+        let app = ctx.app.clone();
+        thread::spawn(move || {
+            info!("creating checkpoint => {}", path);
+            match app.db.create_checkpoint(&path) {
+                Ok(()) => info!("checkpoint created => {}", path),
+                Err(e) => warn!("create checkpoint failed: {}", e),
+            }
+        });
+        Ok(true)
+    }
+
+    /// Compact the database in background
+    fn compact_db(ctx: &Context) -> bool {
+        let app = ctx.app.clone();
+        thread::spawn(move || {
+            info!("compacting db");
+            match app.db.compact_db() {
+                Ok(()) => info!("db compacted"),
+                Err(e) => warn!("compact db failed: {}", e),
+            }
+        });
+        true
+    }
+}
+
+pub(crate) type AdminSchema = juniper::RootNode<'static, AdminQuery, AdminMutation, EmptySubscription<Context>>;
+
+/// Check `Authorization: Bearer <token>` of the request.
+pub(crate) fn is_authorized(req: &Request<Body>, token: &str) -> bool {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.strip_prefix("Bearer "))
+        .map(|val| constant_time_eq(val.as_bytes(), token.as_bytes()))
+        .unwrap_or(false)
+}
+
+/// Compare in time independent of where the inputs differ, so the token can not be guessed byte by byte by timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
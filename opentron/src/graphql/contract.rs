@@ -3,6 +3,9 @@ use keys::b58encode_check;
 use proto2::chain::transaction::Contract as ContractPb;
 use proto2::common::Permission as PermissionPb;
 
+use super::model::Context;
+use crate::abi::DecodedParam;
+
 #[derive(juniper::GraphQLObject)]
 pub struct TransferContract {
     pub owner_address: String,
@@ -166,6 +169,41 @@ pub struct CreateSmartContract {
 }
 
 #[derive(juniper::GraphQLObject)]
+/// A decoded function call parameter.
+pub struct CallParam {
+    name: String,
+    r#type: String,
+    /// Value as string. Addresses in base58check, integers in decimal, bytes in hex.
+    value: String,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Call data decoded with the contract ABI.
+pub struct DecodedCall {
+    /// Function name.
+    name: String,
+    /// Function signature, like `transfer(address,uint256)`.
+    signature: String,
+    params: Vec<CallParam>,
+}
+
+impl DecodedCall {
+    pub fn new(name: String, signature: String, params: Vec<DecodedParam>) -> Self {
+        DecodedCall {
+            name,
+            signature,
+            params: params
+                .into_iter()
+                .map(|param| CallParam {
+                    name: param.name,
+                    r#type: param.ty,
+                    value: param.value.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
 pub struct TriggerSmartContract {
     owner_address: String,
     contract_address: String,
@@ -173,6 +211,50 @@ pub struct TriggerSmartContract {
     call_value: f64,
     call_token_value: f64,
     call_token_id: i32,
+    raw_contract_address: Vec<u8>,
+    raw_data: Vec<u8>,
+    /// To decode call data lazily, only when the field is selected.
+    ctx: Option<Context>,
+}
+
+impl TriggerSmartContract {
+    pub(crate) fn set_context(&mut self, ctx: Context) {
+        self.ctx = Some(ctx);
+    }
+}
+
+#[juniper::graphql_object]
+impl TriggerSmartContract {
+    fn owner_address(&self) -> &str {
+        &self.owner_address
+    }
+
+    fn contract_address(&self) -> &str {
+        &self.contract_address
+    }
+
+    fn data(&self) -> &str {
+        &self.data
+    }
+
+    fn call_value(&self) -> f64 {
+        self.call_value
+    }
+
+    fn call_token_value(&self) -> f64 {
+        self.call_token_value
+    }
+
+    fn call_token_id(&self) -> i32 {
+        self.call_token_id
+    }
+
+    /// Decoded call data, by the contract ABI or well-known TRC20 functions.
+    fn decoded(&self) -> Option<DecodedCall> {
+        self.ctx
+            .as_ref()?
+            .decode_trigger_call(&self.raw_contract_address, &self.raw_data)
+    }
 }
 
 #[derive(juniper::GraphQLEnum, PartialEq, Eq)]
@@ -183,7 +265,7 @@ pub enum AccountType {
 }
 
 impl AccountType {
-    fn from_i32(val: i32) -> Self {
+    pub fn from_i32(val: i32) -> Self {
         match val {
             0 => AccountType::Normal,
             1 => AccountType::AssetIssue,
@@ -496,6 +578,9 @@ impl From<ContractPb> for Contract {
                     data: hex::encode(&cntr.data),
                     call_token_value: cntr.call_token_value as _,
                     call_token_id: cntr.call_token_id as _,
+                    raw_contract_address: cntr.contract_address,
+                    raw_data: cntr.data,
+                    ctx: None,
                 };
                 Contract::TriggerSmartContract(inner)
             }
@@ -0,0 +1,70 @@
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::genesis::Witness as GenesisWitness;
+use crate::state::ChainParameter;
+
+#[derive(juniper::GraphQLObject)]
+/// A witness, known as SR(super representative) when active.
+pub struct Witness {
+    address: String,
+    url: String,
+    /// Is one of the active witnesses producing blocks.
+    is_active: bool,
+    vote_count: f64,
+    /// Is the witness from genesis, with genesis votes. Always true until there's an executor to count votes.
+    is_genesis: bool,
+}
+
+impl Witness {
+    /// A genesis witness, active if it's in the witness schedule.
+    pub fn from_genesis(witness: &GenesisWitness, is_active: bool) -> Self {
+        Witness {
+            address: witness.address.clone(),
+            url: witness.url.clone(),
+            is_active,
+            vote_count: witness.votes as _,
+            is_genesis: true,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A parameter and its value.
+pub struct ChainParameterValue {
+    /// Proposal key of the parameter.
+    id: i32,
+    name: String,
+    value: f64,
+}
+
+impl ChainParameterValue {
+    pub fn new(param: ChainParameter, value: i64) -> Self {
+        ChainParameterValue {
+            id: param.to_i32(),
+            name: format!("{:?}", param),
+            value: value as _,
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Chain parameters, and the maintenance schedule.
+pub struct ChainParameters {
+    /// Are the parameters defaults of the config, without approved proposals applied. Always true until there's an
+    /// executor to maintain the state.
+    is_default: bool,
+    parameters: Vec<ChainParameterValue>,
+    /// Votes are counted and active witnesses are re-elected at each maintenance.
+    next_maintenance_time: DateTime<Utc>,
+}
+
+impl ChainParameters {
+    /// Default parameters of the config.
+    pub fn from_defaults(parameters: Vec<ChainParameterValue>, next_maintenance_time: i64) -> Self {
+        ChainParameters {
+            is_default: true,
+            parameters,
+            next_maintenance_time: Utc.timestamp(next_maintenance_time / 1_000, 0),
+        }
+    }
+}
@@ -1,4 +1,8 @@
+mod admin;
 pub mod contract;
+pub mod governance;
 pub mod model;
 pub mod schema;
 pub mod server;
+pub mod subscription;
+mod websocket;
@@ -1,14 +1,30 @@
+use byteorder::{ByteOrder, BE};
 use chain::{IndexedBlock, IndexedTransaction};
 use chrono::{DateTime, TimeZone, Utc};
 use juniper::FieldResult;
 use keys::Address;
 use primitive_types::H256;
+use prost::Message;
+use proto2::chain::ContractType;
+use proto2::state::transaction_receipt::Result as ReceiptResult;
+use proto2::state::{TransactionLog as TransactionLogPb, TransactionReceipt as TransactionReceiptPb};
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::str;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use super::contract::Contract;
+use super::contract::{Contract, DecodedCall};
+use super::governance::{ChainParameterValue, ChainParameters, Witness};
+use crate::abi;
 use crate::context::AppContext;
+use crate::producer::WitnessSchedule;
+use crate::state::ChainParameter;
+
+const DEFAULT_PAGE_SIZE: i32 = 20;
+const MAX_PAGE_SIZE: i32 = 200;
+
+/// Max block range of transaction queries, all transactions in range might be scanned.
+const MAX_TRANSACTION_BLOCK_RANGE: i64 = 1_000;
 
 #[derive(juniper::GraphQLEnum, PartialEq, Eq)]
 #[repr(i32)]
@@ -51,10 +67,129 @@ pub struct RawTransaction {
 #[derive(juniper::GraphQLObject)]
 /// A transaction of blockchain.
 pub struct UnsignedTransaction {
-    /// Transaction hash.
+    /// Transaction hash, to be signed.
     pub id: String,
     /// Inner transaction.
     pub inner: RawTransaction,
+    /// Protobuf encoded raw transaction in hex, pass it to `broadcast` with signatures.
+    pub raw: String,
+}
+
+impl From<IndexedTransaction> for UnsignedTransaction {
+    fn from(txn: IndexedTransaction) -> Self {
+        let mut raw = Vec::with_capacity(txn.raw.encoded_len());
+        txn.raw.raw_data.as_ref().unwrap().encode(&mut raw).unwrap();
+        let Transaction { id, inner, .. } = Transaction::from(txn);
+        UnsignedTransaction {
+            id,
+            inner,
+            raw: hex::encode(&raw),
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Event log emitted by a smart contract.
+pub struct TransactionLog {
+    /// Emitting contract address.
+    address: String,
+    topics: Vec<String>,
+    data: String,
+}
+
+/// Log addresses are 20-byte TVM addresses.
+fn log_address_to_string(raw: &[u8]) -> String {
+    if raw.len() == 20 {
+        Address::from_tvm_bytes(raw).to_string()
+    } else {
+        hex::encode(raw)
+    }
+}
+
+impl From<TransactionLogPb> for TransactionLog {
+    fn from(log: TransactionLogPb) -> Self {
+        TransactionLog {
+            address: log_address_to_string(&log.address),
+            topics: log.topics.iter().map(|topic| hex::encode(topic)).collect(),
+            data: hex::encode(&log.data),
+        }
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Resource consumed by a transaction, in SUN or resource units.
+pub struct ResourceReceipt {
+    energy_usage: f64,
+    energy_fee: f64,
+    origin_energy_usage: f64,
+    energy_usage_total: f64,
+    bandwidth_usage: f64,
+    bandwidth_fee: f64,
+    multisig_fee: f64,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// Execution result of a transaction.
+pub struct TransactionReceipt {
+    success: bool,
+    block_number: i32,
+    block_timestamp: DateTime<Utc>,
+    /// Total fee, in SUN.
+    fee: f64,
+    /// Resource usage, only available after execution.
+    resource: Option<ResourceReceipt>,
+    /// Created smart contract address.
+    contract_address: Option<String>,
+    /// Created asset(TRC10) id.
+    asset_id: Option<i32>,
+    withdrawal_amount: Option<f64>,
+    unfrozen_amount: Option<f64>,
+    exchange_id: Option<i32>,
+    /// Event logs, only available after execution.
+    logs: Vec<TransactionLog>,
+}
+
+impl From<TransactionReceiptPb> for TransactionReceipt {
+    fn from(receipt: TransactionReceiptPb) -> Self {
+        let mut inner = TransactionReceipt {
+            success: receipt.success,
+            block_number: receipt.block_number as _,
+            block_timestamp: Utc.timestamp(
+                receipt.block_timestamp / 1_000,
+                receipt.block_timestamp as u32 % 1_000 * 1_000_000,
+            ),
+            fee: receipt.fee as _,
+            resource: receipt.resource_receipt.map(|res| ResourceReceipt {
+                energy_usage: res.energy_usage as _,
+                energy_fee: res.energy_fee as _,
+                origin_energy_usage: res.origin_energy_usage as _,
+                energy_usage_total: res.energy_usage_total as _,
+                bandwidth_usage: res.bandwidth_usage as _,
+                bandwidth_fee: res.bandwidth_fee as _,
+                multisig_fee: res.multisig_fee as _,
+            }),
+            contract_address: None,
+            asset_id: None,
+            withdrawal_amount: None,
+            unfrozen_amount: None,
+            exchange_id: None,
+            logs: vec![],
+        };
+        match receipt.result {
+            Some(ReceiptResult::Contract(cntr)) => {
+                if !cntr.created_address.is_empty() {
+                    inner.contract_address = Some(keys::b58encode_check(&cntr.created_address));
+                }
+                inner.logs = cntr.logs.into_iter().map(From::from).collect();
+            }
+            Some(ReceiptResult::CreatedAssetId(id)) => inner.asset_id = Some(id as _),
+            Some(ReceiptResult::WithdrawalAmount(amount)) => inner.withdrawal_amount = Some(amount as _),
+            Some(ReceiptResult::UnfrozenAmount(amount)) => inner.unfrozen_amount = Some(amount as _),
+            Some(ReceiptResult::Exchange(exchange)) => inner.exchange_id = Some(exchange.created_exchange_id as _),
+            None => (),
+        }
+        inner
+    }
 }
 
 #[derive(juniper::GraphQLObject)]
@@ -67,6 +202,10 @@ pub struct Transaction {
     /// Inner transaction.
     inner: RawTransaction,
     contract_return: Option<ContractReturn>,
+    /// Receipt of the transaction.
+    receipt: Option<TransactionReceipt>,
+    /// Is the transaction in a solid block.
+    confirmed: bool,
 }
 
 impl From<IndexedTransaction> for Transaction {
@@ -104,6 +243,8 @@ impl From<IndexedTransaction> for Transaction {
             signatures: raw.signatures.iter().map(|sig| hex::encode(sig)).collect(),
             inner,
             contract_return: Some(ContractReturn::from_i32(result.contract_status)),
+            receipt: None,
+            confirmed: false,
         }
     }
 }
@@ -131,6 +272,43 @@ pub struct Block {
     transactions: Vec<Transaction>,
 }
 
+#[derive(juniper::GraphQLObject)]
+pub struct BlockEdge {
+    pub cursor: String,
+    pub node: Block,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of blocks.
+pub struct BlockConnection {
+    pub edges: Vec<BlockEdge>,
+    pub page_info: PageInfo,
+    /// Number of blocks in the range, blocks of forks at the same height are counted.
+    pub total_count: i32,
+}
+
+#[derive(juniper::GraphQLObject)]
+pub struct TransactionEdge {
+    pub cursor: String,
+    pub node: Transaction,
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A page of transactions.
+pub struct TransactionConnection {
+    pub edges: Vec<TransactionEdge>,
+    pub page_info: PageInfo,
+}
+
+#[derive(juniper::GraphQLInputObject)]
+/// Range of block numbers.
+pub struct BlockRange {
+    /// Start block number, inclusive.
+    pub from: i32,
+    /// End block number, inclusive. Default to the latest block.
+    pub to: Option<i32>,
+}
+
 #[derive(juniper::GraphQLObject)]
 /// Misc node info
 pub struct NodeInfo {
@@ -150,15 +328,35 @@ pub struct NodeInfo {
     total_size: f64,
 }
 
+#[derive(juniper::GraphQLObject)]
+/// Pagination info of a connection.
+pub struct PageInfo {
+    pub has_next_page: bool,
+    /// Cursor of the last edge, pass it as `after` to fetch the next page.
+    pub end_cursor: Option<String>,
+}
+
+/// Max number of contract ABIs with cached function selectors.
+const MAX_CACHED_FUNCTION_TABLES: usize = 1024;
+
 #[derive(Clone)]
 pub(crate) struct Context {
     pub app: Arc<AppContext>,
+    /// contract address => function selectors of its ABI
+    function_tables: Arc<Mutex<HashMap<Vec<u8>, Arc<abi::FunctionTable>>>>,
 }
 
 // To make our context usable by Juniper, we have to implement a marker trait.
 impl juniper::Context for Context {}
 
 impl Context {
+    pub fn new(app: Arc<AppContext>) -> Self {
+        Context {
+            app,
+            function_tables: Default::default(),
+        }
+    }
+
     pub fn get_node_info(&self) -> NodeInfo {
         let ref db = self.app.db;
         NodeInfo {
@@ -182,13 +380,20 @@ impl Context {
             (_, Some(num)) => self.app.db.get_block_by_number(num as _)?,
             (None, None) => self.app.db.highest_block()?,
         };
+        Ok(self.make_block(block))
+    }
 
+    /// Build a block object, with receipts of its transactions.
+    pub fn make_block(&self, block: IndexedBlock) -> Block {
         let IndexedBlock { header, transactions } = block;
         let raw_header = header.raw.raw_data.as_ref().unwrap();
 
-        let transactions = transactions.into_iter().map(From::from).collect();
+        let transactions = transactions
+            .into_iter()
+            .map(|txn| self.get_transaction_with_receipt(txn))
+            .collect();
 
-        Ok(Block {
+        Block {
             id: hex::encode(header.hash.as_bytes()),
             number: header.number() as _,
             timestamp: Utc.timestamp(raw_header.timestamp / 1_000, 0),
@@ -200,12 +405,252 @@ impl Context {
             version: raw_header.version,
             witness_signature: hex::encode(&header.raw.witness_signature),
             transactions: transactions,
-        })
+        }
+    }
+
+    /// Genesis witnesses, labeled as genesis. Votes are not counted since there's no state yet.
+    pub fn get_witnesses(&self, active_only: bool) -> FieldResult<Vec<Witness>> {
+        let genesis = &self.app.genesis_config;
+        let schedule = WitnessSchedule::from_genesis(genesis, self.app.config.chain.parameter.maintenance_interval)?;
+        let is_active = |address: &str| {
+            address
+                .parse::<Address>()
+                .map(|addr| schedule.witnesses().contains(&addr))
+                .unwrap_or(false)
+        };
+        Ok(genesis
+            .witnesses
+            .iter()
+            .filter(|wit| !active_only || is_active(&wit.address))
+            .map(|wit| Witness::from_genesis(wit, is_active(&wit.address)))
+            .collect())
+    }
+
+    /// Default parameters from config, labeled as defaults. Proposals are not applied since there's no state yet.
+    pub fn get_chain_parameters(&self) -> FieldResult<ChainParameters> {
+        let config = &self.app.config.chain.parameter;
+        let maintenance_interval = config.maintenance_interval;
+        let parameters = ChainParameter::default_parameters_from_config(config)
+            .into_iter()
+            .map(|(param, value)| ChainParameterValue::new(param, value))
+            .collect();
+
+        let block_timestamp = |block: IndexedBlock| block.header.raw.raw_data.as_ref().unwrap().timestamp;
+        let genesis_timestamp = block_timestamp(self.app.db.get_genesis_block()?);
+        let head_timestamp = block_timestamp(self.app.db.highest_block()?);
+        // same as java-tron's `updateNextMaintenanceTime`, with a fixed interval
+        let num_rounds = (head_timestamp - genesis_timestamp).max(0) / maintenance_interval;
+        let next_maintenance_time = genesis_timestamp + (num_rounds + 1) * maintenance_interval;
+
+        Ok(ChainParameters::from_defaults(parameters, next_maintenance_time))
     }
 
     pub fn get_transaction(&self, id: String) -> FieldResult<Transaction> {
         let txn_id = H256::from_slice(&hex::decode(&id)?);
-        let txn = self.app.db.get_transaction_by_id(&txn_id).map(From::from)?;
-        Ok(txn)
+        let txn = self.app.db.get_transaction_by_id(&txn_id)?;
+        Ok(self.get_transaction_with_receipt(txn))
+    }
+
+    pub fn get_transaction_with_receipt(&self, txn: IndexedTransaction) -> Transaction {
+        let receipt = self.app.db.get_transaction_receipt(&txn.hash).ok();
+        let mut txn = Transaction::from(txn);
+        txn.confirmed = receipt
+            .as_ref()
+            .map(|receipt| receipt.block_number <= self.app.db.get_solid_block_number())
+            .unwrap_or(false);
+        txn.receipt = receipt.map(From::from);
+        if let Contract::TriggerSmartContract(ref mut cntr) = txn.inner.contract {
+            cntr.set_context(self.clone());
+        }
+        txn
+    }
+
+    /// Decode call data of a `TriggerSmartContract`, with the contract ABI or well-known TRC20 functions.
+    pub fn decode_trigger_call(&self, contract_address: &[u8], data: &[u8]) -> Option<DecodedCall> {
+        if data.len() < 4 {
+            return None;
+        }
+        let (selector, input) = data.split_at(4);
+
+        let functions = self.get_function_table(contract_address);
+        let entry = functions
+            .as_ref()
+            .and_then(|functions| functions.find(selector))
+            .or_else(|| abi::TRC20_FUNCTIONS.find(selector))?;
+        let params = abi::decode_function_input(entry, input).ok()?;
+        Some(DecodedCall::new(entry.name.clone(), abi::entry_signature(entry), params))
+    }
+
+    /// Function selectors of the contract ABI. The ABI is always read, since it might be cleared, selectors are
+    /// cached.
+    fn get_function_table(&self, contract_address: &[u8]) -> Option<Arc<abi::FunctionTable>> {
+        let entries = self
+            .app
+            .db
+            .get_smart_contract(contract_address)
+            .ok()
+            .and_then(|cntr| cntr.abi)
+            .map(|abi| abi.entries);
+        let mut tables = self.function_tables.lock().unwrap();
+        let entries = match entries {
+            Some(entries) if !entries.is_empty() => entries,
+            _ => {
+                tables.remove(contract_address);
+                return None;
+            }
+        };
+        if let Some(table) = tables.get(contract_address) {
+            if table.entries() == entries.as_slice() {
+                return Some(table.clone());
+            }
+        }
+        if tables.len() >= MAX_CACHED_FUNCTION_TABLES {
+            tables.clear();
+        }
+        let table = Arc::new(abi::FunctionTable::new(entries));
+        tables.insert(contract_address.to_vec(), table.clone());
+        Some(table)
+    }
+
+    pub fn get_blocks(
+        &self,
+        from: i32,
+        to: Option<i32>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<BlockConnection> {
+        let (from_block, to_block) = self.block_range(from, to)?;
+        let limit = first.unwrap_or(DEFAULT_PAGE_SIZE).max(0).min(MAX_PAGE_SIZE) as usize;
+
+        let start = match after {
+            Some(cursor) => {
+                // block hash, exclusive
+                let mut key = hex::decode(&cursor)?;
+                if key.len() != 32 {
+                    return Err("invalid cursor".into());
+                }
+                key.push(0);
+                key
+            }
+            None => {
+                let mut key = vec![0u8; 8];
+                BE::write_u64(&mut key[..], from_block as u64);
+                key
+            }
+        };
+
+        let mut headers = self
+            .app
+            .db
+            .get_block_headers_in_range(&start, to_block as u64, limit + 1);
+        let has_next_page = headers.len() > limit;
+        headers.truncate(limit);
+
+        let edges = headers
+            .into_iter()
+            .map(|header| {
+                let cursor = hex::encode(header.hash.as_bytes());
+                let block = self.app.db.get_block_from_header(header)?;
+                Ok(BlockEdge {
+                    cursor,
+                    node: self.make_block(block),
+                })
+            })
+            .collect::<FieldResult<Vec<_>>>()?;
+
+        Ok(BlockConnection {
+            page_info: PageInfo {
+                has_next_page,
+                end_cursor: edges.last().map(|edge| edge.cursor.clone()),
+            },
+            edges,
+            total_count: self.app.db.count_blocks_in_range(from_block as u64, to_block as u64) as _,
+        })
+    }
+
+    pub fn get_transactions(
+        &self,
+        block_range: BlockRange,
+        contract_type: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<TransactionConnection> {
+        let (from_block, to_block) = self.block_range(block_range.from, block_range.to)?;
+        if to_block - from_block >= MAX_TRANSACTION_BLOCK_RANGE {
+            return Err(format!("block range exceeds {}", MAX_TRANSACTION_BLOCK_RANGE).into());
+        }
+        let limit = first.unwrap_or(DEFAULT_PAGE_SIZE).max(0).min(MAX_PAGE_SIZE) as usize;
+
+        // [block_hash, transaction_index: u64]
+        let (start, skip_until) = match after {
+            Some(cursor) => {
+                let key = hex::decode(&cursor)?;
+                if key.len() != 40 {
+                    return Err("invalid cursor".into());
+                }
+                (key[..32].to_vec(), Some(BE::read_u64(&key[32..])))
+            }
+            None => {
+                let mut key = vec![0u8; 8];
+                BE::write_u64(&mut key[..], from_block as u64);
+                (key, None)
+            }
+        };
+
+        let mut edges = vec![];
+        let mut has_next_page = false;
+        let headers = self
+            .app
+            .db
+            .get_block_headers_in_range(&start, to_block as u64, usize::MAX);
+        'outer: for header in headers {
+            let block_hash = header.hash;
+            let block = self.app.db.get_block_from_header(header)?;
+            for (index, txn) in block.transactions.into_iter().enumerate() {
+                if start == block_hash.as_bytes() && skip_until.map(|skip| index as u64 <= skip).unwrap_or(false) {
+                    continue;
+                }
+                if let Some(ref contract_type) = contract_type {
+                    if contract_type_name(&txn).as_ref() != Some(contract_type) {
+                        continue;
+                    }
+                }
+                if edges.len() == limit {
+                    has_next_page = true;
+                    break 'outer;
+                }
+                let mut cursor = block_hash.as_bytes().to_vec();
+                cursor.extend_from_slice(&(index as u64).to_be_bytes());
+                edges.push(TransactionEdge {
+                    cursor: hex::encode(&cursor),
+                    node: self.get_transaction_with_receipt(txn),
+                });
+            }
+        }
+
+        Ok(TransactionConnection {
+            page_info: PageInfo {
+                has_next_page,
+                end_cursor: edges.last().map(|edge| edge.cursor.clone()),
+            },
+            edges,
+        })
+    }
+
+    /// Block number range, `to` defaults to the latest block.
+    fn block_range(&self, from: i32, to: Option<i32>) -> FieldResult<(i64, i64)> {
+        let to_block = to
+            .map(|num| num as i64)
+            .unwrap_or_else(|| self.app.db.get_block_height());
+        let from_block = from as i64;
+        if from_block < 0 || from_block > to_block {
+            return Err("invalid block range".into());
+        }
+        Ok((from_block, to_block))
     }
 }
+
+fn contract_type_name(txn: &IndexedTransaction) -> Option<String> {
+    let contract = txn.raw.raw_data.as_ref()?.contract.as_ref()?;
+    ContractType::from_i32(contract.r#type).map(|typ| format!("{:?}", typ))
+}
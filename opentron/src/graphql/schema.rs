@@ -1,9 +1,27 @@
-//! A schema consists of two types: a query object and a mutation object.
+//! A schema consists of three types: a query object, a mutation object and a subscription object.
 
+use byteorder::{ByteOrder, BE};
+use chain::IndexedTransaction;
+use chrono::Utc;
 use juniper::graphql_value;
 use juniper::{FieldError, FieldResult};
+use keys::Address;
+use prost::Message;
+use prost_types::Any;
+use proto2::chain::{
+    transaction::Contract as ContractPb, transaction::Raw as RawTransaction, ContractType, Transaction as TransactionPb,
+};
+use proto2::common::Vote as VotePb;
+use proto2::contract as contract_pb;
 
-use super::model::{Block, Context, NodeInfo, Transaction};
+use super::contract::ResourceCode;
+use super::governance::{ChainParameters, Witness};
+use super::model::{
+    Block, BlockConnection, BlockRange, Context, NodeInfo, Transaction, TransactionConnection, UnsignedTransaction,
+};
+use super::subscription::Subscription;
+use crate::constants::{MAX_NUM_OF_VOTES, MIN_NUM_OF_FROZEN_DAYS_FOR_RESOURCE};
+use crate::wallet::api::broadcast_transaction;
 
 pub(crate) struct Query;
 
@@ -25,18 +43,147 @@ impl Query {
         ctx.get_block(id, num)
     }
 
+    /// Get the latest solid block, confirmed by more than 70% of active witnesses
+    fn solid_block(ctx: &Context) -> FieldResult<Block> {
+        ctx.get_block(None, Some(ctx.app.db.get_solid_block_number() as _))
+    }
+
     /// Get a transaction
     #[graphql(arguments(id(description = "transaction hash")))]
     fn transaction(ctx: &Context, id: String) -> FieldResult<Transaction> {
         ctx.get_transaction(id)
     }
+
+    /// Get blocks in a range, forks included
+    #[graphql(arguments(
+        from(description = "start block number, inclusive"),
+        to(description = "end block number, inclusive, default to the latest block"),
+        first(description = "max number of blocks to return"),
+        after(description = "cursor of the previous page"),
+    ))]
+    fn blocks(
+        ctx: &Context,
+        from: i32,
+        to: Option<i32>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<BlockConnection> {
+        ctx.get_blocks(from, to, first, after)
+    }
+
+    /// Get transactions in a block range
+    #[graphql(arguments(
+        block_range(description = "block range, at most 1000 blocks"),
+        contract_type(description = "filter by contract type, like `TransferContract`"),
+        first(description = "max number of transactions to return"),
+        after(description = "cursor of the previous page"),
+    ))]
+    fn transactions(
+        ctx: &Context,
+        block_range: BlockRange,
+        contract_type: Option<String>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> FieldResult<TransactionConnection> {
+        ctx.get_transactions(block_range, contract_type, first, after)
+    }
+
+    /// Get witnesses, from genesis until votes are counted
+    #[graphql(arguments(active_only(description = "only active witnesses", default = false)))]
+    fn witnesses(ctx: &Context, active_only: bool) -> FieldResult<Vec<Witness>> {
+        ctx.get_witnesses(active_only)
+    }
+
+    /// Get chain parameters
+    fn chain_parameters(ctx: &Context) -> FieldResult<ChainParameters> {
+        ctx.get_chain_parameters()
+    }
 }
 
-#[derive(juniper::GraphQLInputObject)]
+/// Expiration of built transactions, after the head block. Same as java-tron.
+const DEFAULT_EXPIRATION: i64 = 60 * 1_000;
+/// Fee limit of smart contract triggering, 10 TRX.
+const DEFAULT_FEE_LIMIT: i64 = 10_000_000;
+
+#[derive(juniper::GraphQLInputObject, Default)]
 struct ContractOptions {
     memo: Option<String>,
     permission_id: Option<i32>,
-    fee_limit: Option<i32>,
+    /// Max TRX burnt for energy, in SUN, a decimal string.
+    fee_limit: Option<String>,
+}
+
+#[derive(juniper::GraphQLInputObject)]
+struct VoteInput {
+    witness: String,
+    /// Number of votes, a decimal string.
+    count: String,
+}
+
+fn parse_address(addr: &str) -> FieldResult<Vec<u8>> {
+    Ok(addr.parse::<Address>()?.as_bytes().to_vec())
+}
+
+/// Parse a positive integer amount, like SUN. Amounts are decimal strings, since GraphQL `Int` is 32-bit, and `Float` loses
+/// precision.
+fn parse_amount(amount: &str) -> FieldResult<i64> {
+    match amount.parse::<i64>() {
+        Ok(val) if val > 0 => Ok(val),
+        _ => Err(format!("invalid amount {:?}, must be a positive integer", amount).into()),
+    }
+}
+
+/// Build a transaction, referring to the current head block(TAPOS).
+fn build_transaction<M: Message>(
+    ctx: &Context,
+    contract_type: ContractType,
+    contract: M,
+    options: Option<ContractOptions>,
+) -> FieldResult<UnsignedTransaction> {
+    let ContractOptions {
+        memo,
+        permission_id,
+        fee_limit,
+    } = options.unwrap_or_default();
+    let fee_limit = fee_limit.as_ref().map(|limit| parse_amount(limit)).transpose()?;
+
+    let head_block = ctx.app.db.highest_block()?;
+    let head_timestamp = head_block.header.raw.raw_data.as_ref().unwrap().timestamp;
+    let mut ref_block_bytes = [0u8; 8];
+    BE::write_u64(&mut ref_block_bytes[..], head_block.number() as u64);
+
+    let mut parameter = Vec::with_capacity(contract.encoded_len());
+    contract.encode(&mut parameter)?;
+
+    let raw = RawTransaction {
+        ref_block_bytes: ref_block_bytes[6..].to_vec(),
+        ref_block_hash: head_block.hash().as_bytes()[8..16].to_vec(),
+        expiration: head_timestamp + DEFAULT_EXPIRATION,
+        data: memo.map(String::into_bytes).unwrap_or_default(),
+        contract: Some(ContractPb {
+            r#type: contract_type as i32,
+            parameter: Some(Any {
+                type_url: format!("type.googleapis.com/protocol.{:?}", contract_type),
+                value: parameter,
+            }),
+            permission_id: permission_id.unwrap_or_default(),
+            ..Default::default()
+        }),
+        timestamp: Utc::now().timestamp_millis(),
+        fee_limit: fee_limit.unwrap_or_else(|| {
+            if contract_type == ContractType::TriggerSmartContract {
+                DEFAULT_FEE_LIMIT
+            } else {
+                0
+            }
+        }),
+        ..Default::default()
+    };
+    let txn = IndexedTransaction::from_raw(TransactionPb {
+        raw_data: Some(raw),
+        ..Default::default()
+    });
+    Ok(txn.into())
 }
 
 pub(crate) struct Mutation;
@@ -44,11 +191,7 @@ pub(crate) struct Mutation;
 #[juniper::graphql_object(Context = Context)]
 impl Mutation {
     /// Broadcast a transaction with its signatures.
-    fn broadcast(_ctx: &Context, raw: String, signatures: Vec<String>) -> FieldResult<Transaction> {
-        use chain::IndexedTransaction;
-        use prost::Message;
-        use proto2::chain::{transaction::Raw as RawTransaction, Transaction};
-
+    fn broadcast(ctx: &Context, raw: String, signatures: Vec<String>) -> FieldResult<Transaction> {
         let raw = hex::decode(&raw).map_err(|e| {
             FieldError::new(
                 "fail to parse raw transaction as hex",
@@ -69,7 +212,7 @@ impl Mutation {
             )
         })?;
 
-        let txn = Transaction {
+        let txn = TransactionPb {
             raw_data: Some(raw_txn),
             signatures: signatures
                 .iter()
@@ -86,11 +229,130 @@ impl Mutation {
             ..Default::default()
         };
         let txn = IndexedTransaction::from_raw(txn);
-        // TODO: broadcast
+        broadcast_transaction(&ctx.app, txn.clone()).map_err(|e| {
+            FieldError::new(
+                format!("fail to broadcast transaction: {}", e.message),
+                graphql_value!({ "code": (e.code) }),
+            )
+        })?;
         Ok(txn.into())
     }
+
+    /// Build a TRX transfer.
+    #[graphql(arguments(amount(description = "amount in SUN")))]
+    fn transfer(
+        ctx: &Context,
+        from: String,
+        to: String,
+        amount: String,
+        options: Option<ContractOptions>,
+    ) -> FieldResult<UnsignedTransaction> {
+        let cntr = contract_pb::TransferContract {
+            owner_address: parse_address(&from)?,
+            to_address: parse_address(&to)?,
+            amount: parse_amount(&amount)?,
+        };
+        build_transaction(ctx, ContractType::TransferContract, cntr, options)
+    }
+
+    /// Build a TRC10 token transfer.
+    #[graphql(arguments(amount(description = "amount in the token's smallest unit")))]
+    fn transfer_asset(
+        ctx: &Context,
+        from: String,
+        to: String,
+        token_id: i32,
+        amount: String,
+        options: Option<ContractOptions>,
+    ) -> FieldResult<UnsignedTransaction> {
+        let cntr = contract_pb::TransferAssetContract {
+            owner_address: parse_address(&from)?,
+            to_address: parse_address(&to)?,
+            asset_name: token_id.to_string(),
+            amount: parse_amount(&amount)?,
+        };
+        build_transaction(ctx, ContractType::TransferAssetContract, cntr, options)
+    }
+
+    /// Build a balance freezing, for bandwidth or energy.
+    #[graphql(arguments(
+        amount(description = "amount in SUN"),
+        receiver(description = "delegate the resource to another account"),
+    ))]
+    fn freeze_balance(
+        ctx: &Context,
+        from: String,
+        amount: String,
+        resource: ResourceCode,
+        receiver: Option<String>,
+        options: Option<ContractOptions>,
+    ) -> FieldResult<UnsignedTransaction> {
+        let cntr = contract_pb::FreezeBalanceContract {
+            owner_address: parse_address(&from)?,
+            frozen_balance: parse_amount(&amount)?,
+            frozen_duration: MIN_NUM_OF_FROZEN_DAYS_FOR_RESOURCE as _,
+            resource: match resource {
+                ResourceCode::Bandwidth => 0,
+                ResourceCode::Energy => 1,
+            },
+            receiver_address: receiver
+                .map(|addr| parse_address(&addr))
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        build_transaction(ctx, ContractType::FreezeBalanceContract, cntr, options)
+    }
+
+    /// Build a witness voting, replacing all previous votes.
+    fn vote_witness(
+        ctx: &Context,
+        from: String,
+        votes: Vec<VoteInput>,
+        options: Option<ContractOptions>,
+    ) -> FieldResult<UnsignedTransaction> {
+        if votes.is_empty() || votes.len() > MAX_NUM_OF_VOTES {
+            return Err(format!("number of votes must be in [1, {}]", MAX_NUM_OF_VOTES).into());
+        }
+        let cntr = contract_pb::VoteWitnessContract {
+            owner_address: parse_address(&from)?,
+            votes: votes
+                .iter()
+                .map(|vote| {
+                    Ok(VotePb {
+                        vote_address: parse_address(&vote.witness)?,
+                        vote_count: parse_amount(&vote.count)?,
+                    })
+                })
+                .collect::<FieldResult<Vec<_>>>()?,
+            ..Default::default()
+        };
+        build_transaction(ctx, ContractType::VoteWitnessContract, cntr, options)
+    }
+
+    /// Build a smart contract triggering.
+    #[graphql(arguments(
+        data(description = "ABI encoded call data in hex"),
+        call_value(description = "TRX sent to the contract, in SUN, omitted for none"),
+    ))]
+    fn trigger_contract(
+        ctx: &Context,
+        from: String,
+        contract: String,
+        data: String,
+        call_value: Option<String>,
+        options: Option<ContractOptions>,
+    ) -> FieldResult<UnsignedTransaction> {
+        let cntr = contract_pb::TriggerSmartContract {
+            owner_address: parse_address(&from)?,
+            contract_address: parse_address(&contract)?,
+            data: hex::decode(data.trim_start_matches("0x"))?,
+            call_value: call_value.as_deref().map(parse_amount).transpose()?.unwrap_or_default(),
+            ..Default::default()
+        };
+        build_transaction(ctx, ContractType::TriggerSmartContract, cntr, options)
+    }
 }
 
-// A root schema consists of a query and a mutation.
+// A root schema consists of a query, a mutation and a subscription.
 // Request queries can be executed against a RootNode.
-pub(crate) type Schema = juniper::RootNode<'static, Query, Mutation, juniper::EmptySubscription<Context>>;
+pub(crate) type Schema = juniper::RootNode<'static, Query, Mutation, Subscription>;
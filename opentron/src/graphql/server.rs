@@ -1,3 +1,4 @@
+use chrono::Utc;
 use futures::future::FutureExt;
 use hyper::{
     service::{make_service_fn, service_fn},
@@ -5,13 +6,71 @@ use hyper::{
 };
 use juniper::{EmptySubscription, RootNode};
 use log::{info, warn};
+use serde_json::json;
 use slog::slog_info;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 
+use super::admin::{self, AdminMutation, AdminQuery, AdminSchema};
 use super::model::Context;
 use super::schema::{Mutation, Query, Schema};
+use super::subscription::Subscription;
+use super::websocket::{self, Coordinator};
 use crate::context::AppContext;
+use crate::metrics;
+
+/// Default max lag of the head block for readiness, in seconds.
+const DEFAULT_READY_MAX_BLOCK_LAG: i64 = 60;
+const DEFAULT_READY_MIN_PEERS: usize = 1;
+
+/// Healthy when services are running and RocksDB accepts writes.
+fn check_health(app: &AppContext) -> Result<(), String> {
+    if !app.running.load(Ordering::Relaxed) {
+        return Err("services stopped".into());
+    }
+    if app.db.get_accumulated_db_property("rocksdb.is-write-stopped") > 0 {
+        return Err("db write stopped".into());
+    }
+    Ok(())
+}
+
+/// Ready when healthy, the head block is recent and enough peers are connected.
+fn check_readiness(app: &AppContext) -> Result<(), String> {
+    check_health(app)?;
+
+    let config = &app.config.graphql;
+    let max_block_lag = config.ready_max_block_lag.unwrap_or(DEFAULT_READY_MAX_BLOCK_LAG);
+    let min_peers = config.ready_min_peers.unwrap_or(DEFAULT_READY_MIN_PEERS);
+
+    let head_timestamp = app
+        .db
+        .get_block_headers_by_number(app.db.get_block_height() as u64)
+        .iter()
+        .map(|header| header.timestamp())
+        .max()
+        .ok_or("head block not found")?;
+    let lag = (Utc::now().timestamp_millis() - head_timestamp) / 1_000;
+    if lag > max_block_lag {
+        return Err(format!("head block is {}s behind", lag));
+    }
+    let num_peers = app.peers.len();
+    if num_peers < min_peers {
+        return Err(format!("{} peers connected, requires {}", num_peers, min_peers));
+    }
+    Ok(())
+}
+
+fn status_response(result: Result<(), String>) -> Response<Body> {
+    match result {
+        Ok(()) => Response::new(Body::from(json!({ "status": "ok" }).to_string())),
+        Err(reason) => {
+            let mut response = Response::new(Body::from(json!({ "status": "fail", "reason": reason }).to_string()));
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            response
+        }
+    }
+}
 
 pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
     let config = &ctx.config.graphql;
@@ -23,17 +82,29 @@ pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast
 
     let addr = config.endpoint.parse().expect("malformed endpoint address");
 
-    let root_node: Arc<Schema> = Arc::new(RootNode::new(Query, Mutation, EmptySubscription::new()));
-    let ctx = Arc::new(Context { app: ctx });
+    let root_node: Arc<Schema> = Arc::new(RootNode::new(Query, Mutation, Subscription));
+    let coordinator = Arc::new(Coordinator::new(RootNode::new(Query, Mutation, Subscription)));
+    let admin_node: Arc<AdminSchema> = Arc::new(RootNode::new(AdminQuery, AdminMutation, EmptySubscription::new()));
+    let admin_token = Arc::new(config.admin_token.clone());
+    if admin_token.is_some() {
+        info!("admin api enabled on /admin");
+    }
+    let ctx = Arc::new(Context::new(ctx));
 
     let graphql_service = make_service_fn(move |_| {
         let root_node = root_node.clone();
+        let coordinator = coordinator.clone();
+        let admin_node = admin_node.clone();
+        let admin_token = admin_token.clone();
         let ctx = ctx.clone();
         let logger = slog_scope::logger();
 
         async move {
             Ok::<_, hyper::Error>(service_fn(move |req| {
                 let root_node = root_node.clone();
+                let coordinator = coordinator.clone();
+                let admin_node = admin_node.clone();
+                let admin_token = admin_token.clone();
                 let ctx = ctx.clone();
 
                 slog_info!(
@@ -47,10 +118,33 @@ pub async fn graphql_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast
                 async move {
                     match (req.method(), req.uri().path()) {
                         (&Method::GET, "/") => juniper_hyper::graphiql("/graphql", None).await,
-                        (&Method::GET, "/playground") => juniper_hyper::playground("/graphql", None).await,
+                        (&Method::GET, "/playground") => {
+                            juniper_hyper::playground("/graphql", Some("/subscriptions")).await
+                        }
                         (&Method::GET, "/graphql") | (&Method::POST, "/graphql") => {
+                            let _timer = metrics::GRAPHQL_REQUEST_DURATION
+                                .with_label_values(&["/graphql"])
+                                .start_timer();
                             juniper_hyper::graphql(root_node, ctx, req).await
                         }
+                        (&Method::GET, "/subscriptions") => Ok(websocket::upgrade(req, coordinator, ctx)),
+                        (&Method::GET, "/health") => Ok(status_response(check_health(&ctx.app))),
+                        (&Method::GET, "/ready") => Ok(status_response(check_readiness(&ctx.app))),
+                        (&Method::POST, "/admin") => match *admin_token {
+                            Some(ref token) if admin::is_authorized(&req, token) => {
+                                juniper_hyper::graphql(admin_node, ctx, req).await
+                            }
+                            Some(_) => {
+                                let mut response = Response::new(Body::empty());
+                                *response.status_mut() = StatusCode::UNAUTHORIZED;
+                                Ok(response)
+                            }
+                            None => {
+                                let mut response = Response::new(Body::empty());
+                                *response.status_mut() = StatusCode::NOT_FOUND;
+                                Ok(response)
+                            }
+                        },
                         _ => {
                             let mut response = Response::new(Body::empty());
                             *response.status_mut() = StatusCode::NOT_FOUND;
@@ -0,0 +1,151 @@
+//! Subscriptions, driven by chain events of `ChainDB` and the mempool.
+
+use chain::IndexedTransaction;
+use futures::stream::{self, Stream, StreamExt};
+use juniper::FieldError;
+use keys::Address;
+use proto2::chain::ContractType;
+use std::pin::Pin;
+
+use super::model::{Block, Context, Transaction};
+use crate::db::ChainEvent;
+use crate::wallet::api::owner_address;
+
+type FieldStream<T> = Pin<Box<dyn Stream<Item = Result<T, FieldError>> + Send>>;
+
+#[derive(juniper::GraphQLInputObject)]
+/// Filter of new transactions.
+pub struct TransactionFilter {
+    /// Contract type, like `TransferContract`.
+    pub contract_type: Option<String>,
+    /// Owner address of the contract.
+    pub owner: Option<String>,
+    /// Watch pending transactions in mempool, instead of transactions in new blocks.
+    pub pending: Option<bool>,
+}
+
+impl TransactionFilter {
+    fn matches(&self, txn: &IndexedTransaction) -> bool {
+        let contract = match txn.raw.raw_data.as_ref().and_then(|raw| raw.contract.as_ref()) {
+            Some(contract) => contract,
+            None => return false,
+        };
+        if let Some(ref typ) = self.contract_type {
+            match ContractType::from_i32(contract.r#type) {
+                Some(contract_type) if format!("{:?}", contract_type) == *typ => (),
+                _ => return false,
+            }
+        }
+        if let Some(ref owner) = self.owner {
+            let owner = match owner.parse::<Address>() {
+                Ok(owner) => owner,
+                Err(_) => return false,
+            };
+            match contract
+                .parameter
+                .as_ref()
+                .and_then(|param| owner_address(contract.r#type, &param.value))
+            {
+                Some(raw) if raw == owner.as_bytes() => (),
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+#[derive(juniper::GraphQLObject)]
+/// A chain reorganization, the main chain switched to a longer fork.
+pub struct ChainReorg {
+    /// Block number where the chain forked.
+    fork_at: i32,
+    /// Hashes of blocks dropped from the main chain.
+    purged_blocks: Vec<String>,
+    /// Hashes of blocks on the longest fork.
+    new_blocks: Vec<String>,
+    /// Transactions only included in dropped blocks.
+    orphan_transactions: Vec<String>,
+}
+
+pub(crate) struct Subscription;
+
+#[juniper::graphql_subscription(Context = Context)]
+impl Subscription {
+    /// Blocks inserted into the database, including blocks on forks.
+    async fn new_blocks(ctx: &Context) -> FieldStream<Block> {
+        let ctx = ctx.clone();
+        let stream = ctx.app.db.subscribe().filter_map(move |event| {
+            let block = match event {
+                Ok(ChainEvent::NewBlock(block)) => Some(Ok(ctx.make_block((*block).clone()))),
+                _ => None,
+            };
+            async move { block }
+        });
+        Box::pin(stream)
+    }
+
+    /// Transactions of new blocks, or pending transactions.
+    #[graphql(arguments(filter(description = "filter by contract type and owner address")))]
+    async fn new_transactions(ctx: &Context, filter: Option<TransactionFilter>) -> FieldStream<Transaction> {
+        let ctx = ctx.clone();
+        let filter = filter.unwrap_or(TransactionFilter {
+            contract_type: None,
+            owner: None,
+            pending: None,
+        });
+
+        if filter.pending.unwrap_or(false) {
+            let stream = ctx.app.mempool.subscribe().filter_map(move |id| {
+                let txn = id
+                    .ok()
+                    .and_then(|id| ctx.app.mempool.get(&id))
+                    .filter(|txn| filter.matches(txn))
+                    .map(|txn| Ok(Transaction::from(txn)));
+                async move { txn }
+            });
+            Box::pin(stream)
+        } else {
+            let stream = ctx
+                .app
+                .db
+                .subscribe()
+                .filter_map(|event| async move {
+                    match event {
+                        Ok(ChainEvent::NewBlock(block)) => Some(stream::iter(block.transactions.clone())),
+                        _ => None,
+                    }
+                })
+                .flatten()
+                .filter_map(move |txn| {
+                    let txn = if filter.matches(&txn) {
+                        Some(Ok(ctx.get_transaction_with_receipt(txn)))
+                    } else {
+                        None
+                    };
+                    async move { txn }
+                });
+            Box::pin(stream)
+        }
+    }
+
+    /// Chain reorganizations.
+    async fn chain_reorg(ctx: &Context) -> FieldStream<ChainReorg> {
+        let stream = ctx.app.db.subscribe().filter_map(|event| async move {
+            match event {
+                Ok(ChainEvent::Reorg {
+                    fork_at,
+                    purged_blocks,
+                    new_blocks,
+                    orphan_transactions,
+                }) => Some(Ok(ChainReorg {
+                    fork_at: fork_at as _,
+                    purged_blocks: purged_blocks.iter().map(hex::encode).collect(),
+                    new_blocks: new_blocks.iter().map(hex::encode).collect(),
+                    orphan_transactions: orphan_transactions.iter().map(hex::encode).collect(),
+                })),
+                _ => None,
+            }
+        });
+        Box::pin(stream)
+    }
+}
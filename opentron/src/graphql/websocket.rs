@@ -0,0 +1,213 @@
+//! GraphQL subscriptions over WebSocket, using the `graphql-ws` protocol of subscriptions-transport-ws.
+
+use futures::channel::mpsc;
+use futures::future::{abortable, AbortHandle, FusedFuture, FutureExt};
+use futures::select;
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use hyper::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, UPGRADE};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Request, Response, StatusCode};
+use juniper::http::GraphQLRequest;
+use juniper::{DefaultScalarValue, SubscriptionCoordinator};
+use log::{debug, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::protocol::{Message, Role};
+use tokio_tungstenite::WebSocketStream;
+
+use super::model::Context;
+use super::schema::{Mutation, Query};
+use super::subscription::Subscription;
+
+/// Magic GUID of WebSocket handshake, RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const GRAPHQL_WS_PROTOCOL: &str = "graphql-ws";
+/// Max number of messages queued for a client, a client lagging behind is disconnected.
+const MAX_PENDING_MESSAGES: usize = 256;
+
+pub(crate) type Coordinator =
+    juniper_subscriptions::Coordinator<'static, Query, Mutation, Subscription, Context, DefaultScalarValue>;
+
+#[derive(Deserialize)]
+struct ClientMessage {
+    #[serde(rename = "type")]
+    type_: String,
+    id: Option<String>,
+    payload: Option<Value>,
+}
+
+fn server_message(type_: &str, id: Option<&str>, payload: Option<Value>) -> Message {
+    let mut msg = json!({ "type": type_ });
+    if let Some(id) = id {
+        msg["id"] = Value::String(id.to_owned());
+    }
+    if let Some(payload) = payload {
+        msg["payload"] = payload;
+    }
+    Message::Text(msg.to_string())
+}
+
+/// Queue a message to the client, returns false if the connection is closed. A lagging client is disconnected by
+/// closing the channel, which stops the writer.
+fn send(sender: &mut mpsc::Sender<Message>, msg: Message) -> bool {
+    match sender.try_send(msg) {
+        Ok(()) => true,
+        Err(e) if e.is_full() => {
+            warn!("websocket client lags behind, disconnecting");
+            sender.close_channel();
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+fn accept_key(key: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key);
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    base64::encode(&hasher.finalize())
+}
+
+fn bad_request(message: &'static str) -> Response<Body> {
+    let mut response = Response::new(Body::from(message));
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    response
+}
+
+/// Upgrade the request to WebSocket, subscriptions are served in a background task.
+pub(crate) fn upgrade(req: Request<Body>, coordinator: Arc<Coordinator>, ctx: Arc<Context>) -> Response<Body> {
+    let is_websocket = req
+        .headers()
+        .get(UPGRADE)
+        .and_then(|val| val.to_str().ok())
+        .map(|val| val.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false);
+    let key = match req.headers().get(SEC_WEBSOCKET_KEY) {
+        Some(key) if is_websocket => accept_key(key.as_bytes()),
+        _ => return bad_request("expected a websocket upgrade request"),
+    };
+
+    tokio::spawn(async move {
+        match req.into_body().on_upgrade().await {
+            Ok(upgraded) => {
+                let ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                serve_connection(ws, coordinator, ctx).await;
+            }
+            Err(e) => warn!("websocket upgrade error: {}", e),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, key)
+        .header(SEC_WEBSOCKET_PROTOCOL, GRAPHQL_WS_PROTOCOL)
+        .body(Body::empty())
+        .unwrap()
+}
+
+async fn serve_connection(ws: WebSocketStream<Upgraded>, coordinator: Arc<Coordinator>, ctx: Arc<Context>) {
+    let (mut sink, mut source) = ws.split();
+    let (mut sender, mut receiver) = mpsc::channel::<Message>(MAX_PENDING_MESSAGES);
+
+    let mut writer = tokio::spawn(async move {
+        while let Some(msg) = receiver.next().await {
+            if sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+    })
+    .fuse();
+
+    let mut subscriptions: HashMap<String, AbortHandle> = HashMap::new();
+    loop {
+        let msg = select! {
+            msg = source.next().fuse() => msg,
+            // the client is gone, or disconnected for lagging behind
+            _ = writer => break,
+        };
+        let text = match msg {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(_)) => continue,
+        };
+        let msg = match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(msg) => msg,
+            Err(e) => {
+                let payload = json!({ "message": e.to_string() });
+                send(&mut sender, server_message("connection_error", None, Some(payload)));
+                continue;
+            }
+        };
+
+        match (msg.type_.as_str(), msg.id) {
+            ("connection_init", _) => {
+                send(&mut sender, server_message("connection_ack", None, None));
+                send(&mut sender, server_message("ka", None, None));
+            }
+            ("start", Some(id)) => {
+                let request = match msg.payload.map(serde_json::from_value::<GraphQLRequest>) {
+                    Some(Ok(request)) => request,
+                    _ => {
+                        let payload = json!({ "message": "malformed subscription request" });
+                        send(&mut sender, server_message("error", Some(&id), Some(payload)));
+                        continue;
+                    }
+                };
+                debug!("subscription {} started", id);
+                let (task, handle) =
+                    abortable(run_subscription(id.clone(), request, coordinator.clone(), ctx.clone(), sender.clone()));
+                if let Some(prev) = subscriptions.insert(id, handle) {
+                    prev.abort();
+                }
+                tokio::spawn(task);
+            }
+            ("stop", Some(id)) => {
+                if let Some(handle) = subscriptions.remove(&id) {
+                    handle.abort();
+                    send(&mut sender, server_message("complete", Some(&id), None));
+                }
+            }
+            ("connection_terminate", _) => break,
+            _ => (),
+        }
+    }
+
+    for handle in subscriptions.values() {
+        handle.abort();
+    }
+    // writer exits when all senders are dropped
+    drop(sender);
+    if !writer.is_terminated() {
+        let _ = writer.await;
+    }
+}
+
+async fn run_subscription(
+    id: String,
+    request: GraphQLRequest,
+    coordinator: Arc<Coordinator>,
+    ctx: Arc<Context>,
+    mut sender: mpsc::Sender<Message>,
+) {
+    match coordinator.subscribe(&request, &ctx).await {
+        Ok(mut stream) => {
+            while let Some(response) = stream.next().await {
+                let payload = serde_json::to_value(&response).unwrap_or_default();
+                if !send(&mut sender, server_message("data", Some(&id), Some(payload))) {
+                    return;
+                }
+            }
+        }
+        Err(e) => {
+            let payload = serde_json::to_value(&e).unwrap_or_default();
+            send(&mut sender, server_message("error", Some(&id), Some(payload)));
+        }
+    }
+    send(&mut sender, server_message("complete", Some(&id), None));
+}
@@ -0,0 +1,9 @@
+//! java-tron compatible gRPC API, enabled by the `grpc` feature.
+
+#[cfg(feature = "grpc")]
+pub mod protocol {
+    tonic::include_proto!("protocol");
+}
+pub mod server;
+#[cfg(feature = "grpc")]
+mod service;
@@ -0,0 +1,40 @@
+use log::warn;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::context::AppContext;
+
+#[cfg(feature = "grpc")]
+pub async fn grpc_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
+    use futures::future::FutureExt;
+    use log::{error, info};
+    use tonic::transport::Server;
+
+    use super::protocol::wallet_server::WalletServer;
+    use super::service::WalletService;
+
+    let config = &ctx.config.grpc;
+
+    if !config.enable {
+        warn!("grpc server disabled");
+        return;
+    }
+
+    let addr = config.endpoint.parse().expect("malformed endpoint address");
+    info!("listening on grpc://{}", addr);
+
+    let service = WalletServer::new(WalletService::new(ctx.clone()));
+    let server = Server::builder()
+        .add_service(service)
+        .serve_with_shutdown(addr, shutdown_signal.recv().map(|_| ()));
+    if let Err(e) = server.await {
+        error!("grpc server error: {}", e);
+    }
+}
+
+#[cfg(not(feature = "grpc"))]
+pub async fn grpc_server(ctx: Arc<AppContext>, _shutdown_signal: broadcast::Receiver<()>) {
+    if ctx.config.grpc.enable {
+        warn!("grpc server enabled in config, but not compiled in, rebuild with `--features grpc`");
+    }
+}
@@ -0,0 +1,135 @@
+//! Wallet service, converting between proto2 types and java-tron's API messages.
+
+use chain::{IndexedBlock, IndexedTransaction};
+use primitive_types::H256;
+use proto2::chain::Transaction;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+use super::protocol::{
+    r#return::ResponseCode, wallet_server::Wallet, BlockExtention, BytesMessage, EmptyMessage, NumberMessage, Return,
+    TransactionExtention,
+};
+use crate::context::AppContext;
+use crate::wallet::api::{broadcast_transaction, BroadcastError};
+
+pub struct WalletService {
+    ctx: Arc<AppContext>,
+}
+
+impl WalletService {
+    pub fn new(ctx: Arc<AppContext>) -> Self {
+        WalletService { ctx }
+    }
+}
+
+impl From<&IndexedBlock> for BlockExtention {
+    fn from(block: &IndexedBlock) -> Self {
+        BlockExtention {
+            transactions: block.transactions.iter().map(TransactionExtention::from).collect(),
+            block_header: Some(block.header.raw.clone()),
+            blockid: block.hash().as_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<&IndexedTransaction> for TransactionExtention {
+    fn from(txn: &IndexedTransaction) -> Self {
+        TransactionExtention {
+            transaction: Some(txn.raw.clone()),
+            txid: txn.hash.as_bytes().to_vec(),
+            result: Some(Return {
+                result: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<BroadcastError> for Return {
+    fn from(err: BroadcastError) -> Self {
+        let code = match err.code {
+            "SIGERROR" => ResponseCode::Sigerror,
+            "CONTRACT_VALIDATE_ERROR" => ResponseCode::ContractValidateError,
+            "CONTRACT_EXE_ERROR" => ResponseCode::ContractExeError,
+            "BANDWITH_ERROR" => ResponseCode::BandwithError,
+            "DUP_TRANSACTION_ERROR" => ResponseCode::DupTransactionError,
+            "TAPOS_ERROR" => ResponseCode::TaposError,
+            "TOO_BIG_TRANSACTION_ERROR" => ResponseCode::TooBigTransactionError,
+            "TRANSACTION_EXPIRATION_ERROR" => ResponseCode::TransactionExpirationError,
+            "SERVER_BUSY" => ResponseCode::ServerBusy,
+            "NO_CONNECTION" => ResponseCode::NoConnection,
+            "NOT_ENOUGH_EFFECTIVE_CONNECTION" => ResponseCode::NotEnoughEffectiveConnection,
+            _ => ResponseCode::OtherError,
+        };
+        Return {
+            result: false,
+            code: code as i32,
+            message: err.message.into_bytes(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Wallet for WalletService {
+    async fn broadcast_transaction(&self, request: Request<Transaction>) -> Result<Response<Return>, Status> {
+        let txn = request.into_inner();
+        if txn.raw_data.is_none() {
+            return Ok(Response::new(Return {
+                result: false,
+                code: ResponseCode::OtherError as i32,
+                message: b"raw_data is required".to_vec(),
+            }));
+        }
+
+        let reply = match broadcast_transaction(&self.ctx, IndexedTransaction::from_raw(txn)) {
+            Ok(()) => Return {
+                result: true,
+                ..Default::default()
+            },
+            Err(e) => e.into(),
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn get_now_block2(&self, _request: Request<EmptyMessage>) -> Result<Response<BlockExtention>, Status> {
+        let block = self
+            .ctx
+            .db
+            .highest_block()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(BlockExtention::from(&block)))
+    }
+
+    async fn get_block_by_num2(&self, request: Request<NumberMessage>) -> Result<Response<BlockExtention>, Status> {
+        let num = request.into_inner().num;
+        let reply = if num >= 0 {
+            self.ctx
+                .db
+                .get_block_by_number(num as u64)
+                .map(|block| BlockExtention::from(&block))
+                .unwrap_or_default()
+        } else {
+            BlockExtention::default()
+        };
+        Ok(Response::new(reply))
+    }
+
+    async fn get_transaction_by_id(&self, request: Request<BytesMessage>) -> Result<Response<Transaction>, Status> {
+        let id = request.into_inner().value;
+        if id.len() != 32 {
+            return Err(Status::invalid_argument("transaction id must be 32 bytes"));
+        }
+        let id = H256::from_slice(&id);
+        let reply = self
+            .ctx
+            .db
+            .get_transaction_by_id(&id)
+            .ok()
+            .or_else(|| self.ctx.mempool.get(&id))
+            .map(|txn| txn.raw)
+            .unwrap_or_default();
+        Ok(Response::new(reply))
+    }
+}
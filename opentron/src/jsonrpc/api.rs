@@ -0,0 +1,353 @@
+//! Ethereum compatible JSON-RPC methods.
+//!
+//! Tron addresses are mapped to 20-byte TVM addresses, block and transaction hashes are used as is. `eth_call` and
+//! `eth_getLogs` need TVM execution, and are not served.
+
+use byteorder::{ByteOrder, BE};
+use chain::{IndexedBlock, IndexedTransaction};
+use keys::Address;
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::{transaction::Contract as ContractPb, ContractType};
+use proto2::contract as contract_pb;
+use proto2::state::transaction_receipt::Result as ReceiptResult;
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+
+use crate::context::AppContext;
+use crate::state::created_smart_contract;
+
+pub struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn parse_error() -> Self {
+        RpcError {
+            code: -32700,
+            message: "Parse error".into(),
+        }
+    }
+
+    fn invalid_request() -> Self {
+        RpcError {
+            code: -32600,
+            message: "Invalid request".into(),
+        }
+    }
+
+    fn method_not_found(method: &str) -> Self {
+        RpcError {
+            code: -32601,
+            message: format!("the method {} does not exist/is not available", method),
+        }
+    }
+
+    fn invalid_params<T: ToString>(message: T) -> Self {
+        RpcError {
+            code: -32602,
+            message: message.to_string(),
+        }
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
+/// Handle a JSON-RPC request body, single or batch.
+pub fn handle_request(ctx: &AppContext, body: &[u8]) -> String {
+    let reply = match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Array(calls)) => Value::Array(calls.iter().map(|call| handle_call(ctx, call)).collect()),
+        Ok(call) => handle_call(ctx, &call),
+        Err(_) => to_response(Value::Null, Err(RpcError::parse_error())),
+    };
+    reply.to_string()
+}
+
+fn handle_call(ctx: &AppContext, call: &Value) -> Value {
+    let id = call.get("id").cloned().unwrap_or(Value::Null);
+    let params = call.get("params").cloned().unwrap_or_else(|| json!([]));
+    let result = match call.get("method").and_then(Value::as_str) {
+        Some(method) => dispatch(ctx, method, &params),
+        None => Err(RpcError::invalid_request()),
+    };
+    to_response(id, result)
+}
+
+fn to_response(id: Value, result: RpcResult) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    }
+}
+
+fn dispatch(ctx: &AppContext, method: &str, params: &Value) -> RpcResult {
+    match method {
+        "eth_blockNumber" => Ok(quantity(ctx.db.get_block_height() as u64)),
+        "eth_chainId" => Ok(quantity(chain_id(ctx))),
+        "net_version" => Ok(Value::String(chain_id(ctx).to_string())),
+        "eth_getBlockByNumber" => {
+            let num = parse_block_number(ctx, param(params, 0)?)?;
+            let full = param(params, 1).ok().and_then(Value::as_bool).unwrap_or(false);
+            Ok(ctx
+                .db
+                .get_block_by_number(num)
+                .map(|block| block_to_json(&block, full))
+                .unwrap_or(Value::Null))
+        }
+        "eth_getBlockByHash" => {
+            let hash = parse_hash(param(params, 0)?)?;
+            let full = param(params, 1).ok().and_then(Value::as_bool).unwrap_or(false);
+            Ok(ctx
+                .db
+                .get_block_by_hash(&hash)
+                .map(|block| block_to_json(&block, full))
+                .unwrap_or(Value::Null))
+        }
+        "eth_getTransactionByHash" => {
+            let hash = parse_hash(param(params, 0)?)?;
+            Ok(get_transaction(ctx, &hash).unwrap_or(Value::Null))
+        }
+        "eth_getTransactionReceipt" => {
+            let hash = parse_hash(param(params, 0)?)?;
+            Ok(get_transaction_receipt(ctx, &hash).unwrap_or(Value::Null))
+        }
+        _ => Err(RpcError::method_not_found(method)),
+    }
+}
+
+fn param(params: &Value, index: usize) -> Result<&Value, RpcError> {
+    params
+        .get(index)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing value for required argument {}", index)))
+}
+
+/// Tron uses the last 4 bytes of the genesis block hash as chain id.
+fn chain_id(ctx: &AppContext) -> u64 {
+    ctx.genesis_block_id
+        .as_ref()
+        .map(|blk_id| BE::read_u32(&blk_id.hash[28..]) as u64)
+        .unwrap_or_default()
+}
+
+fn quantity(val: u64) -> Value {
+    Value::String(format!("0x{:x}", val))
+}
+
+fn data<T: AsRef<[u8]>>(raw: T) -> Value {
+    Value::String(format!("0x{}", hex::encode(raw)))
+}
+
+fn address(raw: &[u8]) -> Value {
+    match Address::try_from(raw) {
+        Ok(addr) => data(addr.as_tvm_bytes()),
+        Err(_) => Value::Null,
+    }
+}
+
+fn parse_data(val: &Value) -> Result<Vec<u8>, RpcError> {
+    val.as_str()
+        .ok_or_else(|| RpcError::invalid_params("hex string expected"))
+        .and_then(|s| {
+            let s = s.trim_start_matches("0x");
+            hex::decode(s).map_err(RpcError::invalid_params)
+        })
+}
+
+fn parse_hash(val: &Value) -> Result<H256, RpcError> {
+    let raw = parse_data(val)?;
+    if raw.len() != 32 {
+        return Err(RpcError::invalid_params("hash must be 32 bytes"));
+    }
+    Ok(H256::from_slice(&raw))
+}
+
+fn parse_block_number(ctx: &AppContext, val: &Value) -> Result<u64, RpcError> {
+    match val.as_str() {
+        Some("latest") | Some("pending") | Some("safe") | Some("finalized") => Ok(ctx.db.get_block_height() as u64),
+        Some("earliest") => Ok(0),
+        Some(s) => u64::from_str_radix(s.trim_start_matches("0x"), 16).map_err(RpcError::invalid_params),
+        None => Err(RpcError::invalid_params("block number or tag expected")),
+    }
+}
+
+/// Sender, receiver, value and input of a transaction in Ethereum's view.
+fn transaction_call(txn: &IndexedTransaction) -> (Vec<u8>, Option<Vec<u8>>, i64, Vec<u8>) {
+    // Most system contracts have `owner_address` as field 1.
+    #[derive(Clone, PartialEq, Message)]
+    struct OwnerAddress {
+        #[prost(bytes, tag = "1")]
+        owner_address: Vec<u8>,
+    }
+
+    let contract: &ContractPb = match txn.raw.raw_data.as_ref().and_then(|raw| raw.contract.as_ref()) {
+        Some(contract) => contract,
+        None => return (vec![], None, 0, vec![]),
+    };
+    let raw = contract
+        .parameter
+        .as_ref()
+        .map(|param| &param.value[..])
+        .unwrap_or_default();
+
+    match ContractType::from_i32(contract.r#type) {
+        Some(ContractType::TransferContract) => contract_pb::TransferContract::decode(raw)
+            .map(|cntr| (cntr.owner_address, Some(cntr.to_address), cntr.amount, vec![]))
+            .unwrap_or_default(),
+        Some(ContractType::TransferAssetContract) => contract_pb::TransferAssetContract::decode(raw)
+            .map(|cntr| (cntr.owner_address, Some(cntr.to_address), 0, vec![]))
+            .unwrap_or_default(),
+        Some(ContractType::TriggerSmartContract) => contract_pb::TriggerSmartContract::decode(raw)
+            .map(|cntr| (cntr.owner_address, Some(cntr.contract_address), cntr.call_value, cntr.data))
+            .unwrap_or_default(),
+        Some(ContractType::CreateSmartContract) => contract_pb::CreateSmartContract::decode(raw)
+            .map(|cntr| {
+                let new_contract = cntr.new_contract.unwrap_or_default();
+                (cntr.owner_address, None, new_contract.call_value, new_contract.bytecode)
+            })
+            .unwrap_or_default(),
+        Some(ContractType::AccountUpdateContract) => contract_pb::AccountUpdateContract::decode(raw)
+            .map(|cntr| (cntr.owner_address, None, 0, vec![]))
+            .unwrap_or_default(),
+        Some(ContractType::SetAccountIdContract) => contract_pb::SetAccountIdContract::decode(raw)
+            .map(|cntr| (cntr.owner_address, None, 0, vec![]))
+            .unwrap_or_default(),
+        _ => OwnerAddress::decode(raw)
+            .map(|cntr| (cntr.owner_address, None, 0, vec![]))
+            .unwrap_or_default(),
+    }
+}
+
+fn transaction_to_json(txn: &IndexedTransaction, block: &IndexedBlock, index: usize) -> Value {
+    let (from, to, value, input) = transaction_call(txn);
+    let signature = txn.raw.signatures.first().cloned().unwrap_or_default();
+    let (r, s, v) = if signature.len() == 65 {
+        let v = if signature[64] < 27 {
+            signature[64] + 27
+        } else {
+            signature[64]
+        };
+        (data(&signature[..32]), data(&signature[32..64]), quantity(v as u64))
+    } else {
+        (data([0u8; 32]), data([0u8; 32]), quantity(0))
+    };
+
+    json!({
+        "hash": data(txn.hash),
+        "nonce": quantity(0),
+        "blockHash": data(block.hash()),
+        "blockNumber": quantity(block.number() as u64),
+        "transactionIndex": quantity(index as u64),
+        "from": address(&from),
+        "to": to.map(|to| address(&to)).unwrap_or(Value::Null),
+        "value": quantity(value as u64),
+        "gas": quantity(txn.raw.raw_data.as_ref().map(|raw| raw.fee_limit).unwrap_or_default() as u64),
+        "gasPrice": quantity(0),
+        "input": data(input),
+        "type": quantity(0),
+        "v": v,
+        "r": r,
+        "s": s,
+    })
+}
+
+fn block_to_json(block: &IndexedBlock, full: bool) -> Value {
+    let raw_header = block.header.raw.raw_data.as_ref().unwrap();
+
+    let transactions: Vec<Value> = if full {
+        block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, txn)| transaction_to_json(txn, block, index))
+            .collect()
+    } else {
+        block.transactions.iter().map(|txn| data(txn.hash)).collect()
+    };
+    let state_root = if raw_header.account_state_root.is_empty() {
+        vec![0u8; 32]
+    } else {
+        raw_header.account_state_root.clone()
+    };
+    // genesis block has no parent
+    let parent_hash = if raw_header.parent_hash.len() == 32 {
+        raw_header.parent_hash.clone()
+    } else {
+        vec![0u8; 32]
+    };
+
+    json!({
+        "number": quantity(block.number() as u64),
+        "hash": data(block.hash()),
+        "parentHash": data(parent_hash),
+        "nonce": data([0u8; 8]),
+        // keccak256(rlp([]))
+        "sha3Uncles": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+        "logsBloom": data(&[0u8; 256][..]),
+        "transactionsRoot": data(&raw_header.merkle_root_hash),
+        "stateRoot": data(state_root),
+        "receiptsRoot": data([0u8; 32]),
+        "miner": address(&raw_header.witness_address),
+        "difficulty": quantity(0),
+        "totalDifficulty": quantity(0),
+        "extraData": "0x",
+        "size": quantity(block.size() as u64),
+        "gasLimit": quantity(0),
+        "gasUsed": quantity(0),
+        "timestamp": quantity(raw_header.timestamp as u64 / 1_000),
+        "transactions": transactions,
+        "uncles": [],
+    })
+}
+
+fn get_transaction(ctx: &AppContext, hash: &H256) -> Option<Value> {
+    let (block_hash, index) = ctx.db.get_transaction_position(hash).ok()?;
+    let block = ctx.db.get_block_by_hash(&block_hash).ok()?;
+    let txn = block.transactions.get(index as usize)?;
+    Some(transaction_to_json(txn, &block, index as usize))
+}
+
+fn get_transaction_receipt(ctx: &AppContext, hash: &H256) -> Option<Value> {
+    let (block_hash, index) = ctx.db.get_transaction_position(hash).ok()?;
+    let block = ctx.db.get_block_by_hash(&block_hash).ok()?;
+    let txn = block.transactions.get(index as usize)?;
+    let receipt = ctx.db.get_transaction_receipt(hash).ok()?;
+
+    let (from, to, _, _) = transaction_call(txn);
+    let energy_usage = receipt
+        .resource_receipt
+        .as_ref()
+        .map(|res| res.energy_usage_total)
+        .unwrap_or_default();
+    let contract_address = match receipt.result {
+        Some(ReceiptResult::Contract(ref cntr_receipt)) if cntr_receipt.created_address.is_empty() => {
+            created_smart_contract(txn)
+                .map(|cntr| address(&cntr.contract_address))
+                .unwrap_or(Value::Null)
+        }
+        Some(ReceiptResult::Contract(ref cntr_receipt)) => address(&cntr_receipt.created_address),
+        _ => Value::Null,
+    };
+
+    Some(json!({
+        "transactionHash": data(hash),
+        "transactionIndex": quantity(index),
+        "blockHash": data(block_hash),
+        "blockNumber": quantity(block.number() as u64),
+        "from": address(&from),
+        "to": to.map(|to| address(&to)).unwrap_or(Value::Null),
+        "cumulativeGasUsed": quantity(energy_usage as u64),
+        "gasUsed": quantity(energy_usage as u64),
+        "effectiveGasPrice": quantity(0),
+        "contractAddress": contract_address,
+        // logs come from TVM execution, never stored
+        "logs": [],
+        "logsBloom": data(&[0u8; 256][..]),
+        "status": quantity(receipt.success as u64),
+        "type": quantity(0),
+    }))
+}
@@ -0,0 +1,65 @@
+use futures::future::FutureExt;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Response, Server, StatusCode,
+};
+use log::{info, warn};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::api::handle_request;
+use crate::context::AppContext;
+use crate::util::read_body;
+
+pub async fn json_rpc_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
+    let config = &ctx.config.json_rpc;
+
+    if !config.enable {
+        warn!("json-rpc server disabled");
+        return;
+    }
+
+    let addr = config.endpoint.parse().expect("malformed endpoint address");
+
+    let json_rpc_service = make_service_fn(move |_| {
+        let ctx = ctx.clone();
+
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                let ctx = ctx.clone();
+
+                async move {
+                    match (req.method(), req.uri().path()) {
+                        (&Method::POST, "/") => {
+                            let body = match read_body(req.into_body()).await? {
+                                Some(body) => body,
+                                None => {
+                                    let mut response = Response::new(Body::empty());
+                                    *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+                                    return Ok(response);
+                                }
+                            };
+                            let reply = handle_request(&ctx, &body);
+                            let mut response = Response::new(Body::from(reply));
+                            response
+                                .headers_mut()
+                                .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+                            Ok::<_, hyper::Error>(response)
+                        }
+                        _ => {
+                            let mut response = Response::new(Body::empty());
+                            *response.status_mut() = StatusCode::NOT_FOUND;
+                            Ok(response)
+                        }
+                    }
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(json_rpc_service);
+    info!("listening on http://{}", addr);
+
+    let _ = server.with_graceful_shutdown(shutdown_signal.recv().map(|_| ())).await;
+}
@@ -1,13 +1,22 @@
 #![recursion_limit = "2048"]
 
+pub mod abi;
 pub mod channel;
 pub mod commands;
 pub mod config;
 pub mod context;
 pub mod db;
 pub mod discovery;
+pub mod fork;
 pub mod genesis;
 pub mod graphql;
+pub mod grpc;
+pub mod jsonrpc;
+pub mod mempool;
+pub mod metrics;
+pub mod producer;
+pub mod solidity;
 pub mod state;
 pub mod util;
+pub mod wallet;
 pub mod constants;
@@ -8,7 +8,6 @@ use log::info;
 use slog::{o, Drain};
 use slog_scope_futures::FutureExt as SlogFutureExt;
 use std::error::Error;
-use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -18,7 +17,12 @@ use opentron::channel::server::channel_server;
 use opentron::context::AppContext;
 use opentron::discovery::server::discovery_server;
 use opentron::graphql::server::graphql_server;
+use opentron::grpc::server::grpc_server;
+use opentron::jsonrpc::server::json_rpc_server;
+use opentron::metrics::metrics_server;
+use opentron::producer::producer_service;
 use opentron::util::get_my_ip;
+use opentron::wallet::server::wallet_api_server;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ! init app command line arguments
@@ -52,22 +56,39 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let fut = opentron::commands::check::main(config_file, arg_matches);
             rt.block_on(fut)
         }
+        ("checkpoint", Some(arg_matches)) => {
+            let fut = opentron::commands::checkpoint::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
         ("fix", Some(arg_matches)) => {
             let fut = opentron::commands::fix::main(config_file, arg_matches);
             rt.block_on(fut)
         }
-        _ => {
-            let fut = run(config_file);
+        ("replay", Some(arg_matches)) => {
+            let fut = opentron::commands::replay::main(config_file, arg_matches);
+            rt.block_on(fut)
+        }
+        ("snapshot", Some(arg_matches)) => {
+            let fut = opentron::commands::snapshot::main(config_file, arg_matches);
             rt.block_on(fut)
         }
+        ("devnet", Some(arg_matches)) => {
+            let ctx = opentron::commands::devnet::init_context(config_file, arg_matches)?;
+            rt.block_on(run(ctx))
+        }
+        _ => {
+            let ctx = AppContext::from_config(config_file)?;
+            rt.block_on(run(ctx))
+        }
     }
 }
 
 // NOTE: #[tokio::main] conflicts with slog_scope, cause data race in global static resource release.
-async fn run<P: AsRef<Path>>(config_file: P) -> Result<(), Box<dyn Error>> {
-    let mut ctx = AppContext::from_config(config_file)?;
+async fn run(mut ctx: AppContext) -> Result<(), Box<dyn Error>> {
     info!("load config => \n{:#?}", ctx.config);
-    ctx.outbound_ip = get_my_ip().await?;
+    if ctx.outbound_ip.is_empty() {
+        ctx.outbound_ip = get_my_ip().await?;
+    }
     info!("outbound ip address: {}", ctx.outbound_ip);
     let ctx = Arc::new(ctx);
 
@@ -78,9 +99,7 @@ async fn run<P: AsRef<Path>>(config_file: P) -> Result<(), Box<dyn Error>> {
         let done = done.clone();
         move || {
             let _ = done.send(());
-            while let Some(done) = ctx.peers.write().unwrap().pop() {
-                let _ = done.send(());
-            }
+            ctx.peers.disconnect_all();
             ctx.running.store(false, Ordering::SeqCst);
             ctx.db.report_status();
             unsafe {
@@ -107,6 +126,41 @@ async fn run<P: AsRef<Path>>(config_file: P) -> Result<(), Box<dyn Error>> {
         graphql_server(ctx, done_signal).with_logger(logger)
     };
 
+    let json_rpc_service = {
+        let ctx = ctx.clone();
+        let done_signal = done.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "json-rpc"));
+        json_rpc_server(ctx, done_signal).with_logger(logger)
+    };
+
+    let wallet_api_service = {
+        let ctx = ctx.clone();
+        let done_signal = done.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "http-api"));
+        wallet_api_server(ctx, done_signal).with_logger(logger)
+    };
+
+    let grpc_service = {
+        let ctx = ctx.clone();
+        let done_signal = done.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "grpc"));
+        grpc_server(ctx, done_signal).with_logger(logger)
+    };
+
+    let metrics_service = {
+        let ctx = ctx.clone();
+        let done_signal = done.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "metrics"));
+        metrics_server(ctx, done_signal).with_logger(logger)
+    };
+
+    let producer_service = {
+        let ctx = ctx.clone();
+        let done_signal = done.subscribe();
+        let logger = slog_scope::logger().new(o!("service" => "producer"));
+        producer_service(ctx, done_signal).with_logger(logger)
+    };
+
     let channel_service = {
         let ctx = ctx.clone();
         let done_signal = done.subscribe();
@@ -119,7 +173,16 @@ async fn run<P: AsRef<Path>>(config_file: P) -> Result<(), Box<dyn Error>> {
         let done_signal = done.subscribe();
         discovery_server(ctx, done_signal)
     };
-    let _ = join!(graphql_service, channel_service, discovery_service);
+    let _ = join!(
+        graphql_service,
+        json_rpc_service,
+        wallet_api_service,
+        grpc_service,
+        metrics_service,
+        producer_service,
+        channel_service,
+        discovery_service
+    );
 
     Ok(termination_done.await?)
 }
@@ -0,0 +1,84 @@
+//! Pool of pending transactions, broadcasted by API clients or received from peers.
+
+use chain::{IndexedBlock, IndexedTransaction};
+use primitive_types::H256;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tokio::sync::broadcast;
+
+/// Max number of pending transactions.
+const MAX_PENDING_TRANSACTIONS: usize = 10_000;
+
+pub struct Mempool {
+    txns: RwLock<HashMap<H256, IndexedTransaction>>,
+    announcer: broadcast::Sender<H256>,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        let (announcer, _) = broadcast::channel(1_000);
+        Mempool {
+            txns: RwLock::default(),
+            announcer,
+        }
+    }
+}
+
+impl Mempool {
+    pub fn len(&self) -> usize {
+        self.txns.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= MAX_PENDING_TRANSACTIONS
+    }
+
+    pub fn contains(&self, id: &H256) -> bool {
+        self.txns.read().unwrap().contains_key(id)
+    }
+
+    pub fn get(&self, id: &H256) -> Option<IndexedTransaction> {
+        self.txns.read().unwrap().get(id).cloned()
+    }
+
+    /// All pending transactions, in no particular order.
+    pub fn pending(&self) -> Vec<IndexedTransaction> {
+        self.txns.read().unwrap().values().cloned().collect()
+    }
+
+    /// Add a transaction and announce it to subscribers. Returns false if it's already in pool.
+    pub fn insert(&self, txn: IndexedTransaction) -> bool {
+        let id = txn.hash;
+        if self.txns.write().unwrap().insert(id, txn).is_some() {
+            return false;
+        }
+        // no subscriber is fine
+        let _ = self.announcer.send(id);
+        true
+    }
+
+    /// Remove transactions included in the block, and those expired at the block's timestamp.
+    pub fn remove_included(&self, block: &IndexedBlock) {
+        let timestamp = block
+            .header
+            .raw
+            .raw_data
+            .as_ref()
+            .map(|raw| raw.timestamp)
+            .unwrap_or_default();
+        let mut txns = self.txns.write().unwrap();
+        for txn in &block.transactions {
+            txns.remove(&txn.hash);
+        }
+        txns.retain(|_, txn| txn.raw.raw_data.as_ref().map(|raw| raw.expiration).unwrap_or_default() > timestamp);
+    }
+
+    /// Subscribe to ids of newly added transactions.
+    pub fn subscribe(&self) -> broadcast::Receiver<H256> {
+        self.announcer.subscribe()
+    }
+}
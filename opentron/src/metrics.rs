@@ -0,0 +1,155 @@
+//! Prometheus metrics, served at `/metrics`.
+//!
+//! Counters are updated where events happen, gauges of node state are refreshed on each scrape.
+
+use futures::future::FutureExt;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Response, Server, StatusCode};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use prometheus::core::Collector;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntGauge, IntGaugeVec, Opts, TextEncoder};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::context::AppContext;
+
+/// RocksDB properties, and whether they are accumulated over all column families.
+const ROCKSDB_PROPERTIES: &[(&str, bool)] = &[
+    ("rocksdb.num-running-compactions", false),
+    ("rocksdb.num-running-flushes", false),
+    ("rocksdb.num-immutable-mem-table", true),
+    ("rocksdb.is-write-stopped", true),
+    ("rocksdb.live-sst-files-size", true),
+    ("rocksdb.estimate-num-keys", true),
+];
+
+fn register<T: Collector + Clone + 'static>(metric: T) -> T {
+    prometheus::register(Box::new(metric.clone())).expect("duplicated metric");
+    metric
+}
+
+lazy_static! {
+    pub static ref BLOCKS_INSERTED: IntCounter = register(
+        IntCounter::new("opentron_blocks_inserted_total", "Blocks inserted into the database, forks included").unwrap()
+    );
+    pub static ref TRANSACTIONS_INSERTED: IntCounter = register(
+        IntCounter::new(
+            "opentron_transactions_inserted_total",
+            "Transactions inserted into the database, forks included"
+        )
+        .unwrap()
+    );
+    pub static ref BLOCKS_PRODUCED: IntCounter =
+        register(IntCounter::new("opentron_blocks_produced_total", "Blocks produced by this node").unwrap());
+    pub static ref SLOTS_MISSED: IntCounter =
+        register(IntCounter::new("opentron_slots_missed_total", "Slots of this node missed for lagging").unwrap());
+    pub static ref DISCOVERY_PEERS: IntGauge =
+        register(IntGauge::new("opentron_discovery_peers", "Number of peers in the discovery table").unwrap());
+    pub static ref GRAPHQL_REQUEST_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new("opentron_graphql_request_duration_seconds", "GraphQL request latency"),
+            &["path"]
+        )
+        .unwrap()
+    );
+    static ref HEAD_BLOCK_NUMBER: IntGauge =
+        register(IntGauge::new("opentron_head_block_number", "Block number of the head block").unwrap());
+    static ref SYNC_LAG: IntGauge = register(
+        IntGauge::new("opentron_sync_lag_blocks", "Number of blocks behind the highest block known to connected peers")
+            .unwrap()
+    );
+    static ref PEERS: IntGaugeVec = register(
+        IntGaugeVec::new(Opts::new("opentron_channel_peers", "Number of connected channel peers"), &["direction"])
+            .unwrap()
+    );
+    static ref MEMPOOL_SIZE: IntGauge =
+        register(IntGauge::new("opentron_mempool_transactions", "Number of pending transactions").unwrap());
+    static ref ROCKSDB: IntGaugeVec =
+        register(IntGaugeVec::new(Opts::new("opentron_rocksdb", "RocksDB properties"), &["property"]).unwrap());
+}
+
+fn update_gauges(ctx: &AppContext) {
+    let head_block_number = ctx.db.get_block_height();
+    HEAD_BLOCK_NUMBER.set(head_block_number);
+
+    let peers = ctx.peers.list();
+    let highest_peer_block_number = peers
+        .iter()
+        .map(|info| info.head_block_number)
+        .max()
+        .unwrap_or(head_block_number);
+    SYNC_LAG.set((highest_peer_block_number - head_block_number).max(0));
+    let num_inbound = peers.iter().filter(|info| info.inbound).count();
+    PEERS.with_label_values(&["inbound"]).set(num_inbound as _);
+    PEERS
+        .with_label_values(&["outbound"])
+        .set((peers.len() - num_inbound) as _);
+
+    MEMPOOL_SIZE.set(ctx.mempool.len() as _);
+
+    for &(property, accumulated) in ROCKSDB_PROPERTIES {
+        let value = if accumulated {
+            ctx.db.get_accumulated_db_property(property)
+        } else {
+            ctx.db.get_db_property(property)
+        };
+        ROCKSDB
+            .with_label_values(&[property.trim_start_matches("rocksdb.")])
+            .set(value as _);
+    }
+}
+
+fn encode_metrics(ctx: &AppContext) -> Response<Body> {
+    update_gauges(ctx);
+
+    let encoder = TextEncoder::new();
+    let mut buf = vec![];
+    match encoder.encode(&prometheus::gather(), &mut buf) {
+        Ok(()) => Response::builder()
+            .header(CONTENT_TYPE, encoder.format_type())
+            .body(Body::from(buf))
+            .unwrap(),
+        Err(e) => {
+            let mut response = Response::new(Body::from(e.to_string()));
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        }
+    }
+}
+
+pub async fn metrics_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
+    let config = &ctx.config.metrics;
+
+    if !config.enable {
+        warn!("metrics server disabled");
+        return;
+    }
+
+    let addr = config.endpoint.parse().expect("malformed endpoint address");
+
+    let metrics_service = make_service_fn(move |_| {
+        let ctx = ctx.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                let ctx = ctx.clone();
+                async move {
+                    match (req.method(), req.uri().path()) {
+                        (&Method::GET, "/metrics") => Ok::<_, hyper::Error>(encode_metrics(&ctx)),
+                        _ => {
+                            let mut response = Response::new(Body::empty());
+                            *response.status_mut() = StatusCode::NOT_FOUND;
+                            Ok(response)
+                        }
+                    }
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(metrics_service);
+    info!("listening on http://{}/metrics", addr);
+
+    let _ = server.with_graceful_shutdown(shutdown_signal.recv().map(|_| ())).await;
+}
@@ -0,0 +1,365 @@
+//! Block production of witnesses, known as SR(super representative), in DPoS slots.
+//!
+//! Each slot is 3s. Active witnesses take turns producing blocks, ordered by votes. Without an executor,
+//! votes are never counted, so the active witnesses are the genesis witnesses.
+
+use chain::{merkle_root, IndexedBlock, IndexedBlockHeader, IndexedTransaction};
+use chrono::Utc;
+use futures::future::FutureExt;
+use futures::select;
+use keys::{Address, Private};
+use log::{debug, error, info, warn};
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::{block_header::Raw as BlockHeaderRaw, BlockHeader};
+use std::error::Error;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::time::{delay_for, Duration};
+
+use crate::constants::{
+    BLOCK_PRODUCING_INTERVAL, CURRENT_BLOCK_VERSION, MAX_BLOCK_SIZE, MAX_NUM_OF_ACTIVE_WITNESSES,
+    MAX_TRANSACTION_EXPIRATION, NUM_OF_SKIPPED_SLOTS_IN_MAINTENANCE,
+};
+use crate::context::AppContext;
+use crate::fork::ForkController;
+use crate::genesis::GenesisConfig;
+use crate::metrics;
+use crate::state::ChainParameter;
+
+/// Interval of checking whether it's time to produce a block.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+/// Max delay of producing a block after its slot time, in ms. Otherwise the slot is missed.
+const MAX_PRODUCING_LAG: i64 = 500;
+
+/// Slot schedule of active witnesses.
+pub struct WitnessSchedule {
+    genesis_timestamp: i64,
+    maintenance_interval: i64,
+    /// Active witnesses, ordered by votes.
+    witnesses: Vec<Address>,
+}
+
+impl WitnessSchedule {
+    pub fn new(genesis_timestamp: i64, maintenance_interval: i64, witnesses: Vec<Address>) -> Self {
+        WitnessSchedule {
+            genesis_timestamp,
+            maintenance_interval,
+            witnesses,
+        }
+    }
+
+    /// Active witnesses of genesis, top voted ones.
+    pub fn from_genesis(genesis: &GenesisConfig, maintenance_interval: i64) -> Result<Self, Box<dyn Error>> {
+        let mut witnesses = genesis
+            .witnesses
+            .iter()
+            .map(|wit| Ok((wit.votes, wit.address.parse::<Address>()?)))
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+        witnesses.sort_by(|(lhs, _), (rhs, _)| rhs.cmp(lhs));
+        witnesses.truncate(MAX_NUM_OF_ACTIVE_WITNESSES);
+        if witnesses.is_empty() {
+            return Err("no witness in genesis".into());
+        }
+        Ok(Self::new(
+            genesis.timestamp,
+            maintenance_interval,
+            witnesses.into_iter().map(|(_, addr)| addr).collect(),
+        ))
+    }
+
+    pub fn witnesses(&self) -> &[Address] {
+        &self.witnesses
+    }
+
+    pub fn maintenance_interval(&self) -> i64 {
+        self.maintenance_interval
+    }
+
+    /// Is the block the first block after a maintenance. Slots are skipped after maintenance.
+    pub fn is_maintenance_block(&self, parent_timestamp: i64, timestamp: i64) -> bool {
+        let num_rounds = (parent_timestamp - self.genesis_timestamp).max(0) / self.maintenance_interval;
+        let next_maintenance_time = self.genesis_timestamp + (num_rounds + 1) * self.maintenance_interval;
+        timestamp >= next_maintenance_time
+    }
+
+    /// Time of a slot after the head block, slot 1 is the next slot.
+    pub fn slot_time(&self, head_timestamp: i64, after_maintenance: bool, mut slot: i64) -> i64 {
+        let interval = BLOCK_PRODUCING_INTERVAL as i64;
+        if after_maintenance {
+            slot += NUM_OF_SKIPPED_SLOTS_IN_MAINTENANCE as i64;
+        }
+        let head_slot_time = head_timestamp - (head_timestamp - self.genesis_timestamp) % interval;
+        head_slot_time + slot * interval
+    }
+
+    /// Slot at a time, counted from the head block. 0 if it's not yet time of the next slot.
+    pub fn slot_at_time(&self, head_timestamp: i64, after_maintenance: bool, when: i64) -> i64 {
+        let first_slot_time = self.slot_time(head_timestamp, after_maintenance, 1);
+        if when < first_slot_time {
+            0
+        } else {
+            (when - first_slot_time) / BLOCK_PRODUCING_INTERVAL as i64 + 1
+        }
+    }
+
+    /// Witness of a slot after the head block, the one scheduled at the slot time.
+    pub fn scheduled_witness(&self, head_timestamp: i64, after_maintenance: bool, slot: i64) -> &Address {
+        let slot_time = self.slot_time(head_timestamp, after_maintenance, slot);
+        let absolute_slot = (slot_time - self.genesis_timestamp) / BLOCK_PRODUCING_INTERVAL as i64;
+        let idx = absolute_slot as usize % self.witnesses.len();
+        &self.witnesses[idx]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ProductionCondition {
+    Produced(i64),
+    /// The head block is not the latest, still syncing.
+    NotSynced,
+    NotMyTurn,
+    NotTimeYet,
+    /// Slot is missed, producing lags behind the slot time, in ms.
+    Lag(i64),
+}
+
+struct BlockProducer {
+    ctx: Arc<AppContext>,
+    key: Private,
+    address: Address,
+    schedule: WitnessSchedule,
+    need_sync_check: bool,
+    /// Time of the latest missed slot, reported only once.
+    missed_slot_time: i64,
+}
+
+impl BlockProducer {
+    /// Head block header of the main chain, and whether it's the first block after a maintenance. Fork blocks at
+    /// the same height are ignored.
+    fn head(&self) -> Result<(IndexedBlockHeader, bool), Box<dyn Error>> {
+        let db = &self.ctx.db;
+        let head = db.get_head_block_header()?;
+        if head.number() == 0 {
+            return Ok((head, false));
+        }
+        let parent_hash = H256::from_slice(&head.raw.raw_data.as_ref().unwrap().parent_hash);
+        let parent_timestamp = db.get_block_header_by_hash(&parent_hash)?.timestamp();
+        let after_maintenance = self.schedule.is_maintenance_block(parent_timestamp, head.timestamp());
+        Ok((head, after_maintenance))
+    }
+
+    fn try_produce(&mut self, now: i64) -> Result<ProductionCondition, Box<dyn Error>> {
+        let (head, after_maintenance) = self.head()?;
+        let head_timestamp = head.timestamp();
+
+        if self.need_sync_check {
+            if self.schedule.slot_time(head_timestamp, after_maintenance, 1) > now {
+                self.need_sync_check = false;
+            } else {
+                return Ok(ProductionCondition::NotSynced);
+            }
+        }
+
+        let slot = self.schedule.slot_at_time(head_timestamp, after_maintenance, now);
+        if slot == 0 {
+            return Ok(ProductionCondition::NotTimeYet);
+        }
+        if self.schedule.scheduled_witness(head_timestamp, after_maintenance, slot) != &self.address {
+            return Ok(ProductionCondition::NotMyTurn);
+        }
+        let slot_time = self.schedule.slot_time(head_timestamp, after_maintenance, slot);
+        if now - slot_time > MAX_PRODUCING_LAG {
+            if self.missed_slot_time == slot_time {
+                // waiting for the next slot
+                return Ok(ProductionCondition::NotTimeYet);
+            }
+            self.missed_slot_time = slot_time;
+            return Ok(ProductionCondition::Lag(now - slot_time));
+        }
+        if slot > 1 {
+            info!("{} slots missed before block {}", slot - 1, head.number() + 1);
+        }
+
+        let block = self.generate_block(&head, slot_time)?;
+        let block_number = block.number();
+        self.apply_block(block)?;
+        Ok(ProductionCondition::Produced(block_number))
+    }
+
+    /// Pack pending transactions, unexpired ones of active features, within the block size limit.
+    fn pack_transactions(&self, timestamp: i64, fork: &ForkController) -> Vec<IndexedTransaction> {
+        let mut block_size = 0;
+        let mut txns = vec![];
+        for txn in self.ctx.mempool.pending() {
+            let expiration = txn.raw.raw_data.as_ref().map(|raw| raw.expiration).unwrap_or_default();
+            if expiration <= timestamp || expiration > timestamp + MAX_TRANSACTION_EXPIRATION as i64 {
+                continue;
+            }
+            if self.ctx.db.get_transaction_by_id(&txn.hash).is_ok() {
+                continue;
+            }
+            if !fork.is_active(ChainParameter::AllowMultisig) && permission_id_of(&txn) != 0 {
+                continue;
+            }
+            let txn_size = txn.raw.encoded_len();
+            if block_size + txn_size > MAX_BLOCK_SIZE {
+                continue;
+            }
+            block_size += txn_size;
+            txns.push(txn);
+        }
+        txns
+    }
+
+    fn generate_block(&self, head: &IndexedBlockHeader, timestamp: i64) -> Result<IndexedBlock, Box<dyn Error>> {
+        let fork = ForkController::load(&self.ctx.db, &self.schedule)?;
+        let transactions = self.pack_transactions(timestamp, &fork);
+        let raw_header = BlockHeaderRaw {
+            timestamp,
+            merkle_root_hash: merkle_root(&transactions).as_bytes().to_vec(),
+            parent_hash: head.hash.as_bytes().to_vec(),
+            number: head.number() + 1,
+            witness_address: self.address.as_bytes().to_vec(),
+            // announce the version of this node, as a vote for the fork
+            version: CURRENT_BLOCK_VERSION as i32,
+            ..Default::default()
+        };
+        let mut buf = Vec::with_capacity(255);
+        raw_header.encode(&mut buf)?;
+        let signature = self.key.sign_digest(crypto::sha256(&buf).as_bytes())?;
+
+        let header = BlockHeader {
+            raw_data: Some(raw_header),
+            witness_signature: signature.as_bytes().to_vec(),
+        };
+        Ok(IndexedBlock::new(IndexedBlockHeader::from_raw(header), transactions))
+    }
+
+    fn apply_block(&self, block: IndexedBlock) -> Result<(), Box<dyn Error>> {
+        let ctx = &self.ctx;
+        ctx.recent_blk_ids.write().unwrap().insert(block.header.hash);
+        ctx.db.insert_block(&block)?;
+        ctx.db.update_block_height(block.number());
+        ctx.mempool.remove_included(&block);
+        // no connected peer is fine
+        let _ = ctx.produced_blocks.send(block.header.hash);
+        info!("produce block, number={}, txns={}, hash={}", block.number(), block.transactions.len(), block.hash());
+        Ok(())
+    }
+}
+
+fn permission_id_of(txn: &IndexedTransaction) -> i32 {
+    txn.raw
+        .raw_data
+        .as_ref()
+        .and_then(|raw| raw.contract.as_ref())
+        .map(|contract| contract.permission_id)
+        .unwrap_or_default()
+}
+
+pub async fn producer_service(ctx: Arc<AppContext>, mut signal: broadcast::Receiver<()>) -> Result<(), Box<dyn Error>> {
+    let config = &ctx.config.witness;
+    if !config.enable {
+        warn!("block production disabled");
+        return Ok(());
+    }
+
+    let key = config.private_key.parse::<Private>()?;
+    let address = Address::from_private(&key);
+    let schedule = WitnessSchedule::from_genesis(&ctx.genesis_config, ctx.config.chain.parameter.maintenance_interval)?;
+    if !schedule.witnesses.contains(&address) {
+        error!("{} is not an active witness, block production refused", address);
+        return Err(format!("{} is not an active witness", address).into());
+    }
+    info!("producing blocks as witness {}", address);
+
+    let mut producer = BlockProducer {
+        ctx: ctx.clone(),
+        key,
+        address,
+        schedule,
+        need_sync_check: config.need_sync_check,
+        missed_slot_time: 0,
+    };
+
+    loop {
+        select! {
+            _ = signal.recv().fuse() => {
+                warn!("block production service closed");
+                break;
+            }
+            _ = delay_for(CHECK_INTERVAL).fuse() => {}
+        }
+        if !ctx.running.load(Ordering::Relaxed) {
+            break;
+        }
+
+        match producer.try_produce(Utc::now().timestamp_millis()) {
+            Ok(ProductionCondition::Produced(_)) => metrics::BLOCKS_PRODUCED.inc(),
+            Ok(ProductionCondition::Lag(lag)) => {
+                warn!("slot missed, lag={}ms", lag);
+                metrics::SLOTS_MISSED.inc();
+            }
+            Ok(cond) => debug!("skip producing, {:?}", cond),
+            Err(e) => error!("produce block failed: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS_TIMESTAMP: i64 = 1_529_891_469_000;
+    const MAINTENANCE_INTERVAL: i64 = 6 * 3600 * 1000;
+
+    fn schedule() -> WitnessSchedule {
+        let witnesses = vec![
+            "THKJYuUmMKKARNf7s2VT51g5uPY6KEqnat".parse().unwrap(),
+            "TVDmPWGYxgi5DNeW8hXrzrhY8Y6zgxPNg4".parse().unwrap(),
+            "TWKZN1JJPFydd5rMgMCV5aZTSiwmoksSZv".parse().unwrap(),
+        ];
+        WitnessSchedule::new(GENESIS_TIMESTAMP, MAINTENANCE_INTERVAL, witnesses)
+    }
+
+    #[test]
+    fn test_slot_time() {
+        let schedule = schedule();
+        assert_eq!(schedule.slot_time(GENESIS_TIMESTAMP, false, 1), GENESIS_TIMESTAMP + 3_000);
+        // unaligned head timestamp
+        let head_timestamp = GENESIS_TIMESTAMP + 3_000 * 10 + 200;
+        assert_eq!(schedule.slot_time(head_timestamp, false, 2), GENESIS_TIMESTAMP + 3_000 * 12);
+        assert_eq!(schedule.slot_time(head_timestamp, true, 1), GENESIS_TIMESTAMP + 3_000 * 13);
+
+        assert_eq!(schedule.slot_at_time(head_timestamp, false, head_timestamp + 1_000), 0);
+        assert_eq!(schedule.slot_at_time(head_timestamp, false, GENESIS_TIMESTAMP + 3_000 * 11), 1);
+        assert_eq!(schedule.slot_at_time(head_timestamp, false, GENESIS_TIMESTAMP + 3_000 * 13 + 500), 3);
+    }
+
+    #[test]
+    fn test_scheduled_witness() {
+        let schedule = schedule();
+        let head_timestamp = GENESIS_TIMESTAMP + 3_000;
+        assert_eq!(schedule.scheduled_witness(head_timestamp, false, 1), &schedule.witnesses[2]);
+        assert_eq!(schedule.scheduled_witness(head_timestamp, false, 2), &schedule.witnesses[0]);
+        assert_eq!(schedule.scheduled_witness(head_timestamp, false, 4), &schedule.witnesses[2]);
+        // skipped slots after maintenance, witness of the slot time
+        assert_eq!(schedule.scheduled_witness(head_timestamp, true, 1), &schedule.witnesses[1]);
+        let slot_time = schedule.slot_time(head_timestamp, true, 1);
+        assert_eq!(
+            schedule.scheduled_witness(head_timestamp, true, 1),
+            schedule.scheduled_witness(slot_time - 3_000, false, 1)
+        );
+    }
+
+    #[test]
+    fn test_maintenance_block() {
+        let schedule = schedule();
+        let maintenance_time = GENESIS_TIMESTAMP + MAINTENANCE_INTERVAL;
+        assert!(schedule.is_maintenance_block(maintenance_time - 3_000, maintenance_time));
+        assert!(!schedule.is_maintenance_block(maintenance_time, maintenance_time + 3_000));
+        assert!(!schedule.is_maintenance_block(GENESIS_TIMESTAMP, GENESIS_TIMESTAMP + 3_000));
+    }
+}
@@ -0,0 +1,143 @@
+//! Block solidification. A block is solid once more than 70% of active witnesses have built blocks on it.
+//!
+//! Without vote counting, active witnesses are the genesis witnesses, see `WitnessSchedule::from_genesis`. Only
+//! blocks extending the main chain are counted, blocks on forks are ignored until the chain is switched to them.
+
+use byteorder::{ByteOrder, BE};
+use primitive_types::H256;
+
+use crate::constants::SOLID_THRESHOLD_PERCENT;
+
+/// Solidity state of the main chain, updated in the same write batch as each block.
+#[derive(Debug, PartialEq)]
+pub struct SolidityTracker {
+    /// Hash of the latest main chain block, `None` before the first block is applied.
+    head_hash: Option<H256>,
+    /// Latest block number produced by each active witness, in order of the witness schedule.
+    latest_block_numbers: Vec<i64>,
+}
+
+impl SolidityTracker {
+    pub fn new(num_witnesses: usize) -> Self {
+        SolidityTracker {
+            head_hash: None,
+            latest_block_numbers: vec![0; num_witnesses],
+        }
+    }
+
+    /// Is the block extending the main chain. Any block is accepted before the first one is applied, for dbs
+    /// created before solidity tracking.
+    pub fn is_main_chain(&self, block_number: i64, parent_hash: &[u8]) -> bool {
+        match self.head_hash {
+            _ if block_number == 0 => true,
+            Some(ref head_hash) => head_hash.as_bytes() == parent_hash,
+            None => true,
+        }
+    }
+
+    /// Apply a main chain block of the witness, returns the number of the latest solid block.
+    pub fn apply_block<T: AsRef<[u8]>>(
+        &mut self,
+        witnesses: &[T],
+        witness: &[u8],
+        block_number: i64,
+        block_hash: H256,
+    ) -> i64 {
+        self.head_hash = Some(block_hash);
+        if let Some(slot) = witnesses.iter().position(|wit| wit.as_ref() == witness) {
+            self.latest_block_numbers[slot] = block_number;
+        }
+
+        // same as java-tron, the block built on by more than 70% of active witnesses, witnesses producing no
+        // block count as block 0
+        let mut numbers = self.latest_block_numbers.clone();
+        numbers.sort();
+        let position = numbers.len() * (100 - SOLID_THRESHOLD_PERCENT) / 100;
+        numbers.get(position).cloned().unwrap_or_default()
+    }
+
+    /// [head_hash, [latest_block_number: u64]*]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; 32 + self.latest_block_numbers.len() * 8];
+        if let Some(ref head_hash) = self.head_hash {
+            buf[..32].copy_from_slice(head_hash.as_bytes());
+        }
+        for (i, &num) in self.latest_block_numbers.iter().enumerate() {
+            BE::write_u64(&mut buf[32 + i * 8..], num as u64);
+        }
+        buf
+    }
+
+    /// Main chain head hash of saved state.
+    pub fn head_hash_of(raw: &[u8]) -> Option<H256> {
+        Self::decode(raw, 0).head_hash
+    }
+
+    /// Decode saved state. Latest block numbers are reset if the number of active witnesses changes.
+    pub fn decode(raw: &[u8], num_witnesses: usize) -> Self {
+        let mut tracker = Self::new(num_witnesses);
+        if raw.len() < 32 {
+            return tracker;
+        }
+        tracker.head_hash = Some(H256::from_slice(&raw[..32]));
+        if raw.len() == 32 + num_witnesses * 8 {
+            for (i, num) in tracker.latest_block_numbers.iter_mut().enumerate() {
+                *num = BE::read_u64(&raw[32 + i * 8..]) as i64;
+            }
+        }
+        tracker
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_of(num: i64) -> H256 {
+        H256::from_low_u64_be(num as u64 + 1)
+    }
+
+    #[test]
+    fn test_solidify_with_27_witnesses() {
+        let mut tracker = SolidityTracker::new(27);
+        let witnesses: Vec<_> = (0..27_u8).map(|i| vec![i; 21]).collect();
+        // witness 0 has produced no block yet
+        for num in 1..=26 {
+            let solid_block_number = tracker.apply_block(&witnesses, &witnesses[num as usize], num, hash_of(num));
+            assert_eq!(solid_block_number, (num - 18).max(0));
+        }
+        // witness 0 produces block 27, 19 witnesses built on block 9
+        assert_eq!(tracker.apply_block(&witnesses, &witnesses[0], 27, hash_of(27)), 9);
+        assert_eq!(tracker.apply_block(&witnesses, &witnesses[1], 28, hash_of(28)), 10);
+
+        assert_eq!(SolidityTracker::decode(&tracker.encode(), 27), tracker);
+        assert_eq!(SolidityTracker::head_hash_of(&tracker.encode()), Some(hash_of(28)));
+        let reset = SolidityTracker::decode(&tracker.encode(), 26);
+        assert_eq!(reset.head_hash, Some(hash_of(28)));
+        assert_eq!(reset.latest_block_numbers, vec![0; 26]);
+    }
+
+    #[test]
+    fn test_no_solidify_with_missing_witnesses() {
+        let mut tracker = SolidityTracker::new(27);
+        let witnesses: Vec<_> = (0..27_u8).map(|i| vec![i; 21]).collect();
+        // only 2 of 27 active witnesses are producing, no block is built on by 70% of them
+        for num in 1..=40 {
+            assert_eq!(tracker.apply_block(&witnesses, &witnesses[num as usize % 2], num, hash_of(num)), 0);
+        }
+        // unknown witness
+        assert_eq!(tracker.apply_block(&witnesses, &[0xff; 21], 41, hash_of(41)), 0);
+    }
+
+    #[test]
+    fn test_fork_blocks_are_not_main_chain() {
+        let mut tracker = SolidityTracker::new(1);
+        assert!(tracker.is_main_chain(5, hash_of(4).as_bytes()));
+        tracker.apply_block(&[[0; 21]], &[0; 21], 5, hash_of(5));
+        assert!(tracker.is_main_chain(6, hash_of(5).as_bytes()));
+        // another block at the same height, or a block on it
+        assert!(!tracker.is_main_chain(5, hash_of(4).as_bytes()));
+        assert!(!tracker.is_main_chain(6, H256::repeat_byte(0xab).as_bytes()));
+        assert!(tracker.is_main_chain(0, &[]));
+    }
+}
@@ -0,0 +1,24 @@
+use chain::IndexedTransaction;
+use keys::Address;
+use prost::Message;
+use proto2::chain::ContractType;
+use proto2::common::SmartContract;
+use proto2::contract::CreateSmartContract;
+use std::convert::TryFrom;
+
+/// The smart contract created by a `CreateSmartContract` transaction, with `contract_address` filled.
+pub fn created_smart_contract(txn: &IndexedTransaction) -> Option<SmartContract> {
+    let contract = txn.raw.raw_data.as_ref()?.contract.as_ref()?;
+    if ContractType::from_i32(contract.r#type) != Some(ContractType::CreateSmartContract) {
+        return None;
+    }
+    let cntr = CreateSmartContract::decode(&contract.parameter.as_ref()?.value[..]).ok()?;
+    let mut smart_cntr = cntr.new_contract?;
+    if smart_cntr.contract_address.is_empty() {
+        let owner = Address::try_from(&cntr.owner_address).ok()?;
+        smart_cntr.contract_address = Address::from_contract_creation(txn.hash.as_bytes(), &owner)
+            .as_bytes()
+            .to_vec();
+    }
+    Some(smart_cntr)
+}
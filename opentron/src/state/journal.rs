@@ -0,0 +1,179 @@
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, BE};
+use primitive_types::H256;
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+/// Column families of state, changes to them are journaled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StateColumn {
+    Contract = 1,
+    /// Chain properties in the default column family, like the solid block number.
+    Chain = 2,
+}
+
+impl StateColumn {
+    fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            1 => Some(StateColumn::Contract),
+            2 => Some(StateColumn::Chain),
+            _ => None,
+        }
+    }
+}
+
+/// Previous values of every state key touched by a block, to roll back state precisely on chain fork.
+#[derive(Debug, PartialEq)]
+pub struct UndoJournal {
+    pub block_number: i64,
+    pub block_hash: H256,
+    /// (column, key, previous value), `None` for keys created by the block.
+    entries: Vec<(StateColumn, Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl UndoJournal {
+    pub fn new(block_number: i64, block_hash: H256) -> Self {
+        UndoJournal {
+            block_number,
+            block_hash,
+            entries: vec![],
+        }
+    }
+
+    pub fn is_recorded(&self, column: StateColumn, key: &[u8]) -> bool {
+        self.entries.iter().any(|(col, k, _)| *col == column && k == key)
+    }
+
+    /// Record the previous value of a key. Only the first change in a block is kept, which holds the value before
+    /// the block.
+    pub fn record(&mut self, column: StateColumn, key: &[u8], prev: Option<Vec<u8>>) {
+        if !self.is_recorded(column, key) {
+            self.entries.push((column, key.to_vec(), prev));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries to restore, in reverse order of changes.
+    pub fn undo_entries(&self) -> impl Iterator<Item = (StateColumn, &[u8], Option<&[u8]>)> {
+        self.entries
+            .iter()
+            .rev()
+            .map(|(col, key, prev)| (*col, &key[..], prev.as_ref().map(|val| &val[..])))
+    }
+
+    /// Read block number of an encoded journal, without decoding entries.
+    pub fn block_number_of(raw: &[u8]) -> i64 {
+        BE::read_u64(&raw[..8]) as i64
+    }
+
+    // [block_number: u64, block_hash, [column: u8, key_len: u32, key, has_prev: u8, (prev_len: u32, prev)?]*]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(8 + 32 + self.entries.len() * 64);
+        buf.write_u64::<BE>(self.block_number as u64).unwrap();
+        buf.extend_from_slice(self.block_hash.as_bytes());
+        for (col, key, prev) in &self.entries {
+            buf.push(*col as u8);
+            buf.write_u32::<BE>(key.len() as u32).unwrap();
+            buf.extend_from_slice(key);
+            match prev {
+                Some(val) => {
+                    buf.push(1);
+                    buf.write_u32::<BE>(val.len() as u32).unwrap();
+                    buf.extend_from_slice(val);
+                }
+                None => buf.push(0),
+            }
+        }
+        buf
+    }
+
+    pub fn decode(mut raw: &[u8]) -> io::Result<Self> {
+        fn read_bytes(raw: &mut &[u8]) -> io::Result<Vec<u8>> {
+            let len = raw.read_u32::<BE>()? as usize;
+            let mut buf = vec![0u8; len];
+            raw.read_exact(&mut buf)?;
+            Ok(buf)
+        }
+
+        let block_number = raw.read_u64::<BE>()? as i64;
+        let mut hash = [0u8; 32];
+        raw.read_exact(&mut hash)?;
+        let mut journal = UndoJournal::new(block_number, H256::from(hash));
+        while !raw.is_empty() {
+            let col = StateColumn::from_u8(raw.read_u8()?)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown state column"))?;
+            let key = read_bytes(&mut raw)?;
+            let prev = match raw.read_u8()? {
+                0 => None,
+                _ => Some(read_bytes(&mut raw)?),
+            };
+            journal.entries.push((col, key, prev));
+        }
+        Ok(journal)
+    }
+}
+
+/// State changes pending in a write batch, read before the db so that blocks applied in one batch see changes of
+/// earlier ones.
+#[derive(Debug, Default)]
+pub struct StateOverlay {
+    /// `None` for deleted keys.
+    values: HashMap<(StateColumn, Vec<u8>), Option<Vec<u8>>>,
+}
+
+impl StateOverlay {
+    /// Pending value of a key, `Some(None)` if the key is deleted, `None` if the key is untouched.
+    pub fn get(&self, column: StateColumn, key: &[u8]) -> Option<Option<&[u8]>> {
+        self.values
+            .get(&(column, key.to_vec()))
+            .map(|val| val.as_ref().map(|val| &val[..]))
+    }
+
+    pub fn put(&mut self, column: StateColumn, key: &[u8], val: &[u8]) {
+        self.values.insert((column, key.to_vec()), Some(val.to_vec()));
+    }
+
+    pub fn delete(&mut self, column: StateColumn, key: &[u8]) {
+        self.values.insert((column, key.to_vec()), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_journal_encoding() {
+        let mut journal = UndoJournal::new(42, H256::repeat_byte(0xab));
+        journal.record(StateColumn::Contract, b"created", None);
+        journal.record(StateColumn::Contract, b"changed", Some(b"old".to_vec()));
+        // only the value before the block is kept
+        journal.record(StateColumn::Contract, b"changed", Some(b"newer".to_vec()));
+
+        let raw = journal.encode();
+        assert_eq!(UndoJournal::block_number_of(&raw), 42);
+        let decoded = UndoJournal::decode(&raw).unwrap();
+        assert_eq!(decoded, journal);
+        assert_eq!(
+            decoded.undo_entries().collect::<Vec<_>>(),
+            vec![
+                (StateColumn::Contract, &b"changed"[..], Some(&b"old"[..])),
+                (StateColumn::Contract, &b"created"[..], None),
+            ]
+        );
+        assert!(UndoJournal::decode(&raw[..raw.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_state_overlay() {
+        let mut overlay = StateOverlay::default();
+        assert_eq!(overlay.get(StateColumn::Contract, b"key"), None);
+        overlay.put(StateColumn::Contract, b"key", b"val");
+        assert_eq!(overlay.get(StateColumn::Contract, b"key"), Some(Some(&b"val"[..])));
+        assert_eq!(overlay.get(StateColumn::Chain, b"key"), None);
+        overlay.delete(StateColumn::Contract, b"key");
+        assert_eq!(overlay.get(StateColumn::Contract, b"key"), Some(None));
+    }
+}
@@ -1,5 +1,11 @@
+pub use contract::created_smart_contract;
+pub use journal::{StateColumn, StateOverlay, UndoJournal};
 pub use parameter::ChainParameter;
 pub use property::DynamicProperty;
+pub use receipt::receipt_from_block_transaction;
 
+mod contract;
+mod journal;
 mod parameter;
 mod property;
+mod receipt;
@@ -0,0 +1,61 @@
+use chain::{IndexedBlock, IndexedTransaction};
+use proto2::chain::transaction::result::{ContractStatus, Status};
+use proto2::chain::ContractType;
+use proto2::state::transaction_receipt::{ContractReceipt, ExchangeReceipt, Result as ReceiptResult};
+use proto2::state::TransactionReceipt;
+
+use super::contract::created_smart_contract;
+
+/// Build a receipt from the result fields embedded in a block transaction.
+///
+/// Only fields that java-tron writes back into `Transaction.ret` are available this way. Resource usage, logs
+/// and internal transactions require execution, and are left empty until an executor overwrites the receipt.
+pub fn receipt_from_block_transaction(block: &IndexedBlock, txn: &IndexedTransaction) -> TransactionReceipt {
+    let raw_header = block.header.raw.raw_data.as_ref().unwrap();
+    // NOTE: take first result, same as graphql model
+    let result = txn.raw.result.first().cloned().unwrap_or_default();
+
+    let contract = txn.raw.raw_data.as_ref().and_then(|raw| raw.contract.as_ref());
+    let contract_type = contract.and_then(|cntr| ContractType::from_i32(cntr.r#type));
+
+    let receipt_result = match contract_type {
+        Some(ContractType::CreateSmartContract) | Some(ContractType::TriggerSmartContract) => {
+            let created_address = created_smart_contract(txn)
+                .map(|cntr| cntr.contract_address)
+                .unwrap_or_default();
+            Some(ReceiptResult::Contract(ContractReceipt {
+                status: result.contract_status,
+                created_address,
+                ..Default::default()
+            }))
+        }
+        Some(ContractType::AssetIssueContract) if !result.asset_issue_id.is_empty() => {
+            result.asset_issue_id.parse().ok().map(ReceiptResult::CreatedAssetId)
+        }
+        Some(ContractType::WithdrawBalanceContract) => Some(ReceiptResult::WithdrawalAmount(result.withdraw_amount)),
+        Some(ContractType::UnfreezeBalanceContract) => Some(ReceiptResult::UnfrozenAmount(result.unfreeze_amount)),
+        Some(ContractType::ExchangeCreateContract) |
+        Some(ContractType::ExchangeInjectContract) |
+        Some(ContractType::ExchangeWithdrawContract) |
+        Some(ContractType::ExchangeTransactionContract) => Some(ReceiptResult::Exchange(ExchangeReceipt {
+            created_exchange_id: result.exchange_id,
+            received_amount: result.exchange_received_amount,
+            injected_amount: result.exchange_inject_another_amount,
+            withdrawal_amount: result.exchange_withdraw_another_amount,
+        })),
+        _ => None,
+    };
+
+    TransactionReceipt {
+        id: txn.hash.as_bytes().to_vec(),
+        // System contracts leave contract_status as DEFAULT, smart contracts set SUCCESS.
+        success: result.status != Status::Failed as i32 &&
+            (result.contract_status == ContractStatus::Default as i32 ||
+                result.contract_status == ContractStatus::Success as i32),
+        block_number: block.number(),
+        block_timestamp: raw_header.timestamp,
+        fee: result.fee,
+        resource_receipt: None,
+        result: receipt_result,
+    }
+}
@@ -1,4 +1,6 @@
 use byteorder::{ByteOrder, BE};
+use hyper::body::HttpBody;
+use hyper::Body;
 use proto2::common::Endpoint;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
@@ -33,6 +35,25 @@ pub async fn get_my_ip() -> Result<String, Box<dyn Error>> {
     Ok(ip.origin)
 }
 
+/// Max size of a request body of HTTP APIs, JSON-RPC batch requests included.
+pub const MAX_REQUEST_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Read a request body, returns `None` if it exceeds `MAX_REQUEST_BODY_SIZE`.
+pub async fn read_body(mut body: Body) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if body.size_hint().lower() > MAX_REQUEST_BODY_SIZE as u64 {
+        return Ok(None);
+    }
+    let mut buf = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > MAX_REQUEST_BODY_SIZE {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf))
+}
+
 pub fn block_hash_to_number(hash: &[u8]) -> i64 {
     BE::read_u64(&hash[..8]) as _
 }
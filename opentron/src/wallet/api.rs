@@ -0,0 +1,296 @@
+//! Handlers of `/wallet/*` endpoints.
+
+use byteorder::{ByteOrder, BE};
+use chain::IndexedTransaction;
+use keys::{Address, Public, Signature};
+use primitive_types::H256;
+use prost::Message;
+use proto2::chain::{transaction::Raw as RawTransaction, ContractType, Transaction};
+use serde_json::{json, Value};
+use std::convert::TryFrom;
+use std::sync::atomic::Ordering;
+
+use super::json::{block_to_json, transaction_info_to_json, transaction_to_json};
+use crate::context::AppContext;
+
+/// Max size of a transaction.
+const MAX_TRANSACTION_SIZE: usize = 500 * 1024;
+/// Max duration from now to a transaction's expiration, in ms.
+const MAX_TRANSACTION_EXPIRATION: i64 = 24 * 60 * 60 * 1_000;
+
+/// Handle an API call, returns `None` for unknown endpoints.
+pub fn handle(ctx: &AppContext, name: &str, params: &Value) -> Option<Value> {
+    let visible = param_bool(params, "visible");
+
+    let reply = match name {
+        "getnowblock" => get_block_by_number(ctx, ctx.db.get_block_height(), visible),
+        "getblockbynum" => match param_i64(params, "num") {
+            Some(num) => get_block_by_number(ctx, num, visible),
+            None => error_reply("num is required"),
+        },
+        "getblockbyid" => match param_hash(params, "value") {
+            Some(id) => ctx
+                .db
+                .get_block_by_id(&id)
+                .map(|block| block_to_json(&block, visible))
+                .unwrap_or_else(|_| json!({})),
+            None => error_reply("value must be a block id"),
+        },
+        "gettransactionbyid" => match param_hash(params, "value") {
+            Some(id) => ctx
+                .db
+                .get_transaction_by_id(&id)
+                .ok()
+                .or_else(|| ctx.mempool.get(&id))
+                .map(|txn| transaction_to_json(&txn, visible))
+                .unwrap_or_else(|| json!({})),
+            None => error_reply("value must be a transaction id"),
+        },
+        "gettransactioninfobyid" => match param_hash(params, "value") {
+            Some(id) => ctx
+                .db
+                .get_transaction_receipt(&id)
+                .map(|receipt| transaction_info_to_json(&receipt, visible))
+                .unwrap_or_else(|_| json!({})),
+            None => error_reply("value must be a transaction id"),
+        },
+        "broadcasttransaction" => broadcast_transaction_json(ctx, params),
+        // NOTE: `getaccount` is not served, account state is not maintained without a transaction executor.
+        _ => return None,
+    };
+    Some(reply)
+}
+
+fn error_reply(message: &str) -> Value {
+    json!({ "Error": message })
+}
+
+fn param_bool(params: &Value, key: &str) -> bool {
+    match params.get(key) {
+        Some(Value::Bool(val)) => *val,
+        Some(Value::String(val)) => val == "true",
+        _ => false,
+    }
+}
+
+fn param_i64(params: &Value, key: &str) -> Option<i64> {
+    match params.get(key)? {
+        Value::Number(val) => val.as_i64(),
+        Value::String(val) => val.parse().ok(),
+        _ => None,
+    }
+}
+
+fn param_hash(params: &Value, key: &str) -> Option<H256> {
+    let raw = hex::decode(params.get(key)?.as_str()?).ok()?;
+    if raw.len() == 32 {
+        Some(H256::from_slice(&raw))
+    } else {
+        None
+    }
+}
+
+fn get_block_by_number(ctx: &AppContext, num: i64, visible: bool) -> Value {
+    if num < 0 {
+        return json!({});
+    }
+    ctx.db
+        .get_block_by_number(num as u64)
+        .map(|block| block_to_json(&block, visible))
+        .unwrap_or_else(|_| json!({}))
+}
+
+/// Failure of transaction broadcasting, `code` is the name of java-tron's `Return.response_code`.
+#[derive(Debug)]
+pub struct BroadcastError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl BroadcastError {
+    fn new<T: ToString>(code: &'static str, message: T) -> Self {
+        BroadcastError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Verify a transaction and add it to mempool, to be announced to peers.
+pub fn broadcast_transaction(ctx: &AppContext, txn: IndexedTransaction) -> Result<(), BroadcastError> {
+    if txn.raw.encoded_len() > MAX_TRANSACTION_SIZE {
+        return Err(BroadcastError::new("TOO_BIG_TRANSACTION_ERROR", "transaction size is too big"));
+    }
+    if ctx.mempool.contains(&txn.hash) || ctx.db.get_transaction_position(&txn.hash).is_ok() {
+        return Err(BroadcastError::new("DUP_TRANSACTION_ERROR", "dup trans"));
+    }
+
+    let head_block = ctx
+        .db
+        .highest_block()
+        .map_err(|e| BroadcastError::new("OTHER_ERROR", e))?;
+    let raw = txn
+        .raw
+        .raw_data
+        .as_ref()
+        .ok_or_else(|| BroadcastError::new("OTHER_ERROR", "raw_data is required"))?;
+    let head_timestamp = head_block.header.raw.raw_data.as_ref().unwrap().timestamp;
+    if raw.expiration <= head_timestamp {
+        return Err(BroadcastError::new("TRANSACTION_EXPIRATION_ERROR", "transaction expired"));
+    }
+    if raw.expiration > head_timestamp + MAX_TRANSACTION_EXPIRATION {
+        return Err(BroadcastError::new("TRANSACTION_EXPIRATION_ERROR", "expiration time is too long"));
+    }
+    check_tapos(ctx, raw, head_block.number()).map_err(|msg| BroadcastError::new("TAPOS_ERROR", msg))?;
+    check_signatures(&txn).map_err(|msg| BroadcastError::new("SIGERROR", msg))?;
+
+    if ctx.num_active_connections.load(Ordering::SeqCst) == 0 {
+        return Err(BroadcastError::new("NO_CONNECTION", "no connection"));
+    }
+    if ctx.mempool.is_full() {
+        return Err(BroadcastError::new("SERVER_BUSY", "server busy"));
+    }
+
+    ctx.mempool.insert(txn);
+    Ok(())
+}
+
+fn broadcast_error_reply(txid: Option<&H256>, err: BroadcastError) -> Value {
+    let mut reply = json!({
+        "code": err.code,
+        "message": hex::encode(err.message),
+    });
+    if let Some(txid) = txid {
+        reply["txid"] = Value::String(hex::encode(txid));
+    }
+    reply
+}
+
+/// Accepts a transaction in JSON, only `raw_data_hex` and `signature` are used.
+fn broadcast_transaction_json(ctx: &AppContext, params: &Value) -> Value {
+    let raw_data = match params
+        .get("raw_data_hex")
+        .and_then(Value::as_str)
+        .and_then(|raw| hex::decode(raw).ok())
+        .and_then(|raw| RawTransaction::decode(&raw[..]).ok())
+    {
+        Some(raw_data) => raw_data,
+        None => {
+            return broadcast_error_reply(None, BroadcastError::new("OTHER_ERROR", "raw_data_hex is required"));
+        }
+    };
+    let signatures = params
+        .get("signature")
+        .and_then(Value::as_array)
+        .map(|sigs| {
+            sigs.iter()
+                .filter_map(Value::as_str)
+                .filter_map(|sig| hex::decode(sig).ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let txn = IndexedTransaction::from_raw(Transaction {
+        raw_data: Some(raw_data),
+        signatures,
+        ..Default::default()
+    });
+    let txid = txn.hash;
+
+    match broadcast_transaction(ctx, txn) {
+        Ok(()) => json!({ "result": true, "txid": hex::encode(txid) }),
+        Err(e) => broadcast_error_reply(Some(&txid), e),
+    }
+}
+
+/// Reference block must be one of the recent 65536 blocks.
+fn check_tapos(ctx: &AppContext, raw: &RawTransaction, head_block_number: i64) -> Result<(), String> {
+    if raw.ref_block_bytes.len() != 2 || raw.ref_block_hash.len() != 8 {
+        return Err("malformed ref block".into());
+    }
+    let ref_num = BE::read_u16(&raw.ref_block_bytes) as i64;
+    let mut block_number = (head_block_number & !0xffff) | ref_num;
+    if block_number > head_block_number {
+        block_number -= 0x10000;
+    }
+    let ref_block_hash = ctx
+        .db
+        .get_block_by_number(block_number.max(0) as u64)
+        .map(|block| block.hash().as_bytes()[8..16].to_vec())
+        .unwrap_or_default();
+    if ref_block_hash != raw.ref_block_hash {
+        return Err(format!(
+            "Tapos check error, ref_block_bytes={}, ref_block_hash={}",
+            hex::encode(&raw.ref_block_bytes),
+            hex::encode(&raw.ref_block_hash)
+        ));
+    }
+    Ok(())
+}
+
+/// Signatures must be recoverable, and signed by owner. Only the default owner permission is accepted, other
+/// permissions can not be checked without account state.
+fn check_signatures(txn: &IndexedTransaction) -> Result<(), String> {
+    if txn.raw.signatures.is_empty() {
+        return Err("miss sig or contract".into());
+    }
+    let signers = txn
+        .raw
+        .signatures
+        .iter()
+        .map(|sig| {
+            let mut sig = sig.clone();
+            // v = 27/28 from Ethereum style signers
+            if sig.len() == 65 && sig[64] >= 27 {
+                sig[64] -= 27;
+            }
+            Signature::try_from(&sig[..])
+                .and_then(|sig| Public::recover_digest(txn.hash.as_bytes(), &sig))
+                .map(|public| Address::from_public(&public))
+                .map_err(|e| format!("validate signature error: {:?}", e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let contract = txn
+        .raw
+        .raw_data
+        .as_ref()
+        .and_then(|raw| raw.contract.as_ref())
+        .ok_or_else(|| "miss sig or contract".to_owned())?;
+    if contract.permission_id != 0 {
+        return Err(format!(
+            "validate signature error: permission {} can not be checked without account state",
+            contract.permission_id
+        ));
+    }
+    let owner = contract
+        .parameter
+        .as_ref()
+        .and_then(|param| owner_address(contract.r#type, &param.value))
+        .ok_or_else(|| "invalid contract".to_owned())?;
+    if signers.iter().all(|signer| signer.as_bytes() != &owner[..]) {
+        return Err("validate signature error: not signed by owner".into());
+    }
+    Ok(())
+}
+
+/// Owner address of a system contract.
+pub fn owner_address(typ: i32, raw: &[u8]) -> Option<Vec<u8>> {
+    #[derive(Clone, PartialEq, Message)]
+    struct OwnerAddressAt1 {
+        #[prost(bytes, tag = "1")]
+        owner_address: Vec<u8>,
+    }
+    #[derive(Clone, PartialEq, Message)]
+    struct OwnerAddressAt2 {
+        #[prost(bytes, tag = "2")]
+        owner_address: Vec<u8>,
+    }
+
+    match ContractType::from_i32(typ)? {
+        ContractType::TransferAssetContract |
+        ContractType::AccountUpdateContract |
+        ContractType::SetAccountIdContract => OwnerAddressAt2::decode(raw).ok().map(|cntr| cntr.owner_address),
+        _ => OwnerAddressAt1::decode(raw).ok().map(|cntr| cntr.owner_address),
+    }
+}
@@ -0,0 +1,674 @@
+//! JSON encoding of java-tron's HTTP API, i.e. `JsonFormat` with `visible` switch.
+//!
+//! Field names follow java-tron's protobuf definitions, and default values are omitted. Keys are kept in insertion
+//! order, the order of protobuf fields like `JsonFormat`, by serde_json's `preserve_order` feature.
+
+use chain::{IndexedBlock, IndexedTransaction};
+use keys::b58encode_check;
+use prost::Message;
+use proto2::chain::transaction::result::{ContractStatus, Status};
+use proto2::chain::transaction::{Contract as ContractPb, Result as ResultPb};
+use proto2::chain::ContractType;
+use proto2::common::smart_contract::abi::{EntryType, Param as AbiParam, StateMutabilityType};
+use proto2::common::{permission::PermissionType, AccountType, Permission, ResourceCode, SmartContract};
+use proto2::contract as contract_pb;
+use proto2::state::transaction_receipt::Result as ReceiptResult;
+use proto2::state::TransactionReceipt;
+use serde_json::{json, Map, Value};
+
+/// A JSON object builder omitting default values.
+pub struct JsonObject {
+    visible: bool,
+    inner: Map<String, Value>,
+}
+
+impl JsonObject {
+    pub fn new(visible: bool) -> Self {
+        JsonObject {
+            visible,
+            inner: Map::new(),
+        }
+    }
+
+    pub fn bytes(mut self, key: &str, raw: &[u8]) -> Self {
+        if !raw.is_empty() {
+            self.inner.insert(key.into(), Value::String(hex::encode(raw)));
+        }
+        self
+    }
+
+    /// Address, base58check encoded when `visible`.
+    pub fn address(mut self, key: &str, raw: &[u8]) -> Self {
+        if !raw.is_empty() {
+            self.inner
+                .insert(key.into(), Value::String(format_address(raw, self.visible)));
+        }
+        self
+    }
+
+    /// Human readable bytes, like names and urls. Plain string when `visible`.
+    pub fn text(mut self, key: &str, raw: &[u8]) -> Self {
+        if !raw.is_empty() {
+            let val = if self.visible {
+                String::from_utf8_lossy(raw).into_owned()
+            } else {
+                hex::encode(raw)
+            };
+            self.inner.insert(key.into(), Value::String(val));
+        }
+        self
+    }
+
+    pub fn string(mut self, key: &str, val: &str) -> Self {
+        if !val.is_empty() {
+            self.inner.insert(key.into(), Value::String(val.into()));
+        }
+        self
+    }
+
+    pub fn int(mut self, key: &str, val: i64) -> Self {
+        if val != 0 {
+            self.inner.insert(key.into(), Value::from(val));
+        }
+        self
+    }
+
+    pub fn bool(mut self, key: &str, val: bool) -> Self {
+        if val {
+            self.inner.insert(key.into(), Value::Bool(val));
+        }
+        self
+    }
+
+    /// Enum value by name, omitted when it's the default (0) variant.
+    pub fn enumeration<T: Into<String>>(mut self, key: &str, code: i32, name: Option<T>) -> Self {
+        if code != 0 {
+            let name = name.map(Into::into).unwrap_or_else(|| code.to_string());
+            self.inner.insert(key.into(), Value::String(name));
+        }
+        self
+    }
+
+    pub fn object(mut self, key: &str, val: Option<Value>) -> Self {
+        if let Some(val) = val {
+            self.inner.insert(key.into(), val);
+        }
+        self
+    }
+
+    pub fn array(mut self, key: &str, vals: Vec<Value>) -> Self {
+        if !vals.is_empty() {
+            self.inner.insert(key.into(), Value::Array(vals));
+        }
+        self
+    }
+
+    pub fn build(self) -> Value {
+        Value::Object(self.inner)
+    }
+}
+
+fn variant_name<T: std::fmt::Debug>(val: T) -> String {
+    format!("{:?}", val)
+}
+
+pub fn format_address(raw: &[u8], visible: bool) -> String {
+    if visible {
+        b58encode_check(raw)
+    } else {
+        hex::encode(raw)
+    }
+}
+
+pub fn block_to_json(block: &IndexedBlock, visible: bool) -> Value {
+    let header = &block.header.raw;
+    let raw_header = header.raw_data.as_ref().unwrap();
+
+    let raw_data = JsonObject::new(visible)
+        .int("timestamp", raw_header.timestamp)
+        .bytes("txTrieRoot", &raw_header.merkle_root_hash)
+        .bytes("parentHash", &raw_header.parent_hash)
+        .int("number", raw_header.number)
+        .address("witness_address", &raw_header.witness_address)
+        .int("version", raw_header.version as i64)
+        .bytes("accountStateRoot", &raw_header.account_state_root)
+        .build();
+    let block_header = JsonObject::new(visible)
+        .object("raw_data", Some(raw_data))
+        .bytes("witness_signature", &header.witness_signature)
+        .build();
+
+    JsonObject::new(visible)
+        .string("blockID", &hex::encode(block.hash()))
+        .object("block_header", Some(block_header))
+        .array(
+            "transactions",
+            block
+                .transactions
+                .iter()
+                .map(|txn| transaction_to_json(txn, visible))
+                .collect(),
+        )
+        .build()
+}
+
+pub fn transaction_to_json(txn: &IndexedTransaction, visible: bool) -> Value {
+    let raw = txn.raw.raw_data.as_ref().unwrap();
+
+    let mut raw_data_hex = Vec::with_capacity(raw.encoded_len());
+    raw.encode(&mut raw_data_hex).unwrap();
+
+    let raw_data = JsonObject::new(visible)
+        .array(
+            "contract",
+            raw.contract
+                .as_ref()
+                .map(|cntr| contract_to_json(cntr, visible))
+                .into_iter()
+                .collect(),
+        )
+        .bytes("ref_block_bytes", &raw.ref_block_bytes)
+        .int("ref_block_num", raw.ref_block_num)
+        .bytes("ref_block_hash", &raw.ref_block_hash)
+        .int("expiration", raw.expiration)
+        .text("data", &raw.data)
+        .bytes("scripts", &raw.scripts)
+        .int("timestamp", raw.timestamp)
+        .int("fee_limit", raw.fee_limit)
+        .build();
+
+    let mut obj = JsonObject::new(visible)
+        .array("ret", txn.raw.result.iter().map(transaction_result_to_json).collect())
+        .array(
+            "signature",
+            txn.raw
+                .signatures
+                .iter()
+                .map(|sig| Value::String(hex::encode(sig)))
+                .collect(),
+        )
+        .string("txID", &hex::encode(txn.hash))
+        .object("raw_data", Some(raw_data))
+        .string("raw_data_hex", &hex::encode(raw_data_hex))
+        .build();
+    obj["visible"] = Value::Bool(visible);
+    obj
+}
+
+fn transaction_result_to_json(ret: &ResultPb) -> Value {
+    JsonObject::new(false)
+        .int("fee", ret.fee)
+        .enumeration("ret", ret.status, Status::from_i32(ret.status).map(|_| "FAILED"))
+        .enumeration(
+            "contractRet",
+            ret.contract_status,
+            ContractStatus::from_i32(ret.contract_status).map(contract_status_name),
+        )
+        .string("assetIssueID", &ret.asset_issue_id)
+        .int("withdraw_amount", ret.withdraw_amount)
+        .int("unfreeze_amount", ret.unfreeze_amount)
+        .int("exchange_received_amount", ret.exchange_received_amount)
+        .int("exchange_inject_another_amount", ret.exchange_inject_another_amount)
+        .int("exchange_withdraw_another_amount", ret.exchange_withdraw_another_amount)
+        .int("exchange_id", ret.exchange_id)
+        .int("shielded_transaction_fee", ret.shielded_transaction_fee)
+        .build()
+}
+
+/// Names of `contractResult` used by java-tron.
+fn contract_status_name(status: ContractStatus) -> &'static str {
+    match status {
+        ContractStatus::Default => "DEFAULT",
+        ContractStatus::Success => "SUCCESS",
+        ContractStatus::Revert => "REVERT",
+        ContractStatus::BadJumpDestination => "BAD_JUMP_DESTINATION",
+        ContractStatus::OutOfMemory => "OUT_OF_MEMORY",
+        ContractStatus::PrecompiledContract => "PRECOMPILED_CONTRACT",
+        ContractStatus::StackTooSmall => "STACK_TOO_SMALL",
+        ContractStatus::StackTooLarge => "STACK_TOO_LARGE",
+        ContractStatus::IllegalOperation => "ILLEGAL_OPERATION",
+        ContractStatus::StackOverflow => "STACK_OVERFLOW",
+        ContractStatus::OutOfEnergy => "OUT_OF_ENERGY",
+        ContractStatus::OutOfTime => "OUT_OF_TIME",
+        ContractStatus::JvmStackOverFlow => "JVM_STACK_OVER_FLOW",
+        ContractStatus::Unknown => "UNKNOWN",
+        ContractStatus::TransferFailed => "TRANSFER_FAILED",
+    }
+}
+
+fn contract_to_json(cntr: &ContractPb, visible: bool) -> Value {
+    let type_name = ContractType::from_i32(cntr.r#type)
+        .map(|typ| format!("{:?}", typ))
+        .unwrap_or_else(|| cntr.r#type.to_string());
+    // NOTE: java-tron uses `ClearABIContract`.
+    let type_name = if type_name == "ClearAbiContract" {
+        "ClearABIContract".to_owned()
+    } else {
+        type_name
+    };
+
+    let value = cntr
+        .parameter
+        .as_ref()
+        .and_then(|param| contract_value_to_json(cntr.r#type, &param.value, visible))
+        .unwrap_or_else(|| json!({}));
+    let parameter = json!({
+        "value": value,
+        "type_url": format!("type.googleapis.com/protocol.{}", type_name),
+    });
+
+    JsonObject::new(visible)
+        .object("parameter", Some(parameter))
+        .string("type", &type_name)
+        .bytes("provider", &cntr.provider)
+        .text("ContractName", &cntr.contract_name)
+        .int("Permission_id", cntr.permission_id as i64)
+        .build()
+}
+
+fn contract_value_to_json(typ: i32, raw: &[u8], visible: bool) -> Option<Value> {
+    let obj = JsonObject::new(visible);
+
+    let value = match ContractType::from_i32(typ)? {
+        ContractType::AccountCreateContract => {
+            let cntr = contract_pb::AccountCreateContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("account_address", &cntr.account_address)
+                .enumeration("type", cntr.r#type, AccountType::from_i32(cntr.r#type).map(variant_name))
+        }
+        ContractType::TransferContract => {
+            let cntr = contract_pb::TransferContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("to_address", &cntr.to_address)
+                .int("amount", cntr.amount)
+        }
+        ContractType::TransferAssetContract => {
+            let cntr = contract_pb::TransferAssetContract::decode(raw).ok()?;
+            obj.text("asset_name", cntr.asset_name.as_bytes())
+                .address("owner_address", &cntr.owner_address)
+                .address("to_address", &cntr.to_address)
+                .int("amount", cntr.amount)
+        }
+        ContractType::VoteAssetContract => {
+            let cntr = contract_pb::VoteAssetContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .array(
+                    "vote_address",
+                    cntr.vote_address
+                        .iter()
+                        .map(|addr| Value::String(format_address(addr, visible)))
+                        .collect(),
+                )
+                .bool("support", cntr.is_support)
+                .int("count", cntr.count as i64)
+        }
+        ContractType::VoteWitnessContract => {
+            let cntr = contract_pb::VoteWitnessContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .array(
+                    "votes",
+                    cntr.votes
+                        .iter()
+                        .map(|vote| {
+                            JsonObject::new(visible)
+                                .address("vote_address", &vote.vote_address)
+                                .int("vote_count", vote.vote_count)
+                                .build()
+                        })
+                        .collect(),
+                )
+                .bool("support", cntr.is_support)
+        }
+        ContractType::WitnessCreateContract => {
+            let cntr = contract_pb::WitnessCreateContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address).text("url", &cntr.url)
+        }
+        ContractType::WitnessUpdateContract => {
+            let cntr = contract_pb::WitnessUpdateContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .text("update_url", &cntr.update_url)
+        }
+        ContractType::AssetIssueContract => {
+            let cntr = contract_pb::AssetIssueContract::decode(raw).ok()?;
+            obj.string("id", &cntr.id)
+                .address("owner_address", &cntr.owner_address)
+                .text("name", cntr.name.as_bytes())
+                .text("abbr", cntr.abbr.as_bytes())
+                .int("total_supply", cntr.total_supply)
+                .array(
+                    "frozen_supply",
+                    cntr.frozen_supply
+                        .iter()
+                        .map(|sup| {
+                            JsonObject::new(visible)
+                                .int("frozen_amount", sup.frozen_amount)
+                                .int("frozen_days", sup.frozen_days)
+                                .build()
+                        })
+                        .collect(),
+                )
+                .int("trx_num", cntr.trx_num as i64)
+                .int("precision", cntr.precision as i64)
+                .int("num", cntr.num as i64)
+                .int("start_time", cntr.start_time)
+                .int("end_time", cntr.end_time)
+                .int("order", cntr.order)
+                .int("vote_score", cntr.vote_score as i64)
+                .text("description", &cntr.description)
+                .text("url", cntr.url.as_bytes())
+                .int("free_asset_net_limit", cntr.free_asset_net_limit)
+                .int("public_free_asset_net_limit", cntr.public_free_asset_net_limit)
+                .int("public_free_asset_net_usage", cntr.public_free_asset_net_usage)
+                .int("public_latest_free_net_time", cntr.public_latest_free_net_time)
+        }
+        ContractType::ParticipateAssetIssueContract => {
+            let cntr = contract_pb::ParticipateAssetIssueContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("to_address", &cntr.to_address)
+                .text("asset_name", cntr.asset_name.as_bytes())
+                .int("amount", cntr.amount)
+        }
+        ContractType::AccountUpdateContract => {
+            let cntr = contract_pb::AccountUpdateContract::decode(raw).ok()?;
+            obj.text("account_name", cntr.account_name.as_bytes())
+                .address("owner_address", &cntr.owner_address)
+        }
+        ContractType::FreezeBalanceContract => {
+            let cntr = contract_pb::FreezeBalanceContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("frozen_balance", cntr.frozen_balance)
+                .int("frozen_duration", cntr.frozen_duration)
+                .enumeration("resource", cntr.resource, ResourceCode::from_i32(cntr.resource).map(|_| "ENERGY"))
+                .address("receiver_address", &cntr.receiver_address)
+        }
+        ContractType::UnfreezeBalanceContract => {
+            let cntr = contract_pb::UnfreezeBalanceContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .enumeration("resource", cntr.resource, ResourceCode::from_i32(cntr.resource).map(|_| "ENERGY"))
+                .address("receiver_address", &cntr.receiver_address)
+        }
+        ContractType::WithdrawBalanceContract => {
+            let cntr = contract_pb::WithdrawBalanceContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+        }
+        ContractType::UnfreezeAssetContract => {
+            let cntr = contract_pb::UnfreezeAssetContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+        }
+        ContractType::UpdateAssetContract => {
+            let cntr = contract_pb::UpdateAssetContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .text("description", &cntr.description)
+                .text("url", &cntr.url)
+                .int("new_limit", cntr.new_limit)
+                .int("new_public_limit", cntr.new_public_limit)
+        }
+        ContractType::ProposalCreateContract => {
+            let cntr = contract_pb::ProposalCreateContract::decode(raw).ok()?;
+            let mut params: Vec<_> = cntr.parameters.iter().collect();
+            params.sort();
+            obj.address("owner_address", &cntr.owner_address).array(
+                "parameters",
+                params
+                    .into_iter()
+                    .map(|(&key, &value)| JsonObject::new(visible).int("key", key).int("value", value).build())
+                    .collect(),
+            )
+        }
+        ContractType::ProposalApproveContract => {
+            let cntr = contract_pb::ProposalApproveContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("proposal_id", cntr.proposal_id)
+                .bool("is_add_approval", cntr.is_approval)
+        }
+        ContractType::ProposalDeleteContract => {
+            let cntr = contract_pb::ProposalDeleteContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("proposal_id", cntr.proposal_id)
+        }
+        ContractType::SetAccountIdContract => {
+            let cntr = contract_pb::SetAccountIdContract::decode(raw).ok()?;
+            obj.text("account_id", &cntr.account_id)
+                .address("owner_address", &cntr.owner_address)
+        }
+        ContractType::CreateSmartContract => {
+            let cntr = contract_pb::CreateSmartContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .object(
+                    "new_contract",
+                    cntr.new_contract
+                        .as_ref()
+                        .map(|cntr| smart_contract_to_json(cntr, visible)),
+                )
+                .int("call_token_value", cntr.call_token_value)
+                .int("token_id", cntr.call_token_id)
+        }
+        ContractType::TriggerSmartContract => {
+            let cntr = contract_pb::TriggerSmartContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("contract_address", &cntr.contract_address)
+                .int("call_value", cntr.call_value)
+                .bytes("data", &cntr.data)
+                .int("call_token_value", cntr.call_token_value)
+                .int("token_id", cntr.call_token_id)
+        }
+        ContractType::UpdateSettingContract => {
+            let cntr = contract_pb::UpdateSettingContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("contract_address", &cntr.contract_address)
+                .int("consume_user_resource_percent", cntr.consume_user_resource_percent)
+        }
+        ContractType::ExchangeCreateContract => {
+            let cntr = contract_pb::ExchangeCreateContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .text("first_token_id", &cntr.first_token_id)
+                .int("first_token_balance", cntr.first_token_balance)
+                .text("second_token_id", &cntr.second_token_id)
+                .int("second_token_balance", cntr.second_token_balance)
+        }
+        ContractType::ExchangeInjectContract => {
+            let cntr = contract_pb::ExchangeInjectContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("exchange_id", cntr.exchange_id)
+                .text("token_id", &cntr.token_id)
+                .int("quant", cntr.quant)
+        }
+        ContractType::ExchangeWithdrawContract => {
+            let cntr = contract_pb::ExchangeWithdrawContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("exchange_id", cntr.exchange_id)
+                .text("token_id", &cntr.token_id)
+                .int("quant", cntr.quant)
+        }
+        ContractType::ExchangeTransactionContract => {
+            let cntr = contract_pb::ExchangeTransactionContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("exchange_id", cntr.exchange_id)
+                .text("token_id", &cntr.token_id)
+                .int("quant", cntr.quant)
+                .int("expected", cntr.expected)
+        }
+        ContractType::UpdateEnergyLimitContract => {
+            let cntr = contract_pb::UpdateEnergyLimitContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("contract_address", &cntr.contract_address)
+                .int("origin_energy_limit", cntr.origin_energy_limit)
+        }
+        ContractType::AccountPermissionUpdateContract => {
+            let cntr = contract_pb::AccountPermissionUpdateContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .object("owner", cntr.owner.as_ref().map(|perm| permission_to_json(perm, visible)))
+                .object("witness", cntr.witness.as_ref().map(|perm| permission_to_json(perm, visible)))
+                .array(
+                    "actives",
+                    cntr.actives
+                        .iter()
+                        .map(|perm| permission_to_json(perm, visible))
+                        .collect(),
+                )
+        }
+        ContractType::ClearAbiContract => {
+            let cntr = contract_pb::ClearAbiContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .address("contract_address", &cntr.contract_address)
+        }
+        ContractType::UpdateBrokerageContract => {
+            let cntr = contract_pb::UpdateBrokerageContract::decode(raw).ok()?;
+            obj.address("owner_address", &cntr.owner_address)
+                .int("brokerage", cntr.brokerage as i64)
+        }
+        // CustomContract, ShieldedTransferContract
+        _ => return None,
+    };
+    Some(value.build())
+}
+
+fn permission_to_json(perm: &Permission, visible: bool) -> Value {
+    JsonObject::new(visible)
+        .enumeration("type", perm.r#type, PermissionType::from_i32(perm.r#type).map(variant_name))
+        .int("id", perm.id as i64)
+        .string("permission_name", &perm.permission_name)
+        .int("threshold", perm.threshold)
+        .int("parent_id", perm.parent_id as i64)
+        .bytes("operations", &perm.operations)
+        .array(
+            "keys",
+            perm.keys
+                .iter()
+                .map(|key| {
+                    JsonObject::new(visible)
+                        .address("address", &key.address)
+                        .int("weight", key.weight)
+                        .build()
+                })
+                .collect(),
+        )
+        .build()
+}
+
+fn smart_contract_to_json(cntr: &SmartContract, visible: bool) -> Value {
+    let abi_param_to_json = |param: &AbiParam| {
+        JsonObject::new(visible)
+            .bool("indexed", param.indexed)
+            .string("name", &param.name)
+            .string("type", &param.r#type)
+            .build()
+    };
+    let abi = cntr.abi.as_ref().map(|abi| {
+        JsonObject::new(visible)
+            .array(
+                "entrys",
+                abi.entries
+                    .iter()
+                    .map(|entry| {
+                        JsonObject::new(visible)
+                            .bool("anonymous", entry.anonymous)
+                            .bool("constant", entry.constant)
+                            .string("name", &entry.name)
+                            .array("inputs", entry.inputs.iter().map(abi_param_to_json).collect())
+                            .array("outputs", entry.outputs.iter().map(abi_param_to_json).collect())
+                            .enumeration("type", entry.r#type, EntryType::from_i32(entry.r#type).map(variant_name))
+                            .bool("payable", entry.payable)
+                            .enumeration(
+                                "stateMutability",
+                                entry.state_mutability,
+                                StateMutabilityType::from_i32(entry.state_mutability).map(variant_name),
+                            )
+                            .build()
+                    })
+                    .collect(),
+            )
+            .build()
+    });
+
+    JsonObject::new(visible)
+        .address("origin_address", &cntr.origin_address)
+        .address("contract_address", &cntr.contract_address)
+        .object("abi", abi)
+        .bytes("bytecode", &cntr.bytecode)
+        .int("call_value", cntr.call_value)
+        .int("consume_user_resource_percent", cntr.consume_user_resource_percent)
+        .string("name", &cntr.name)
+        .int("origin_energy_limit", cntr.origin_energy_limit)
+        .bytes("code_hash", &cntr.code_hash)
+        .bytes("trx_hash", &cntr.txn_id)
+        .build()
+}
+
+/// `TransactionInfo` of java-tron.
+pub fn transaction_info_to_json(receipt: &TransactionReceipt, visible: bool) -> Value {
+    let mut obj = JsonObject::new(visible)
+        .string("id", &hex::encode(&receipt.id))
+        .int("fee", receipt.fee)
+        .int("blockNumber", receipt.block_number)
+        .int("blockTimeStamp", receipt.block_timestamp);
+
+    let res = receipt.resource_receipt.clone().unwrap_or_default();
+    let mut resource_receipt = JsonObject::new(visible)
+        .int("energy_usage", res.energy_usage)
+        .int("energy_fee", res.energy_fee)
+        .int("origin_energy_usage", res.origin_energy_usage)
+        .int("energy_usage_total", res.energy_usage_total)
+        .int("net_usage", res.bandwidth_usage)
+        .int("net_fee", res.bandwidth_fee);
+
+    match receipt.result {
+        Some(ReceiptResult::Contract(ref cntr_receipt)) => {
+            resource_receipt = resource_receipt.enumeration(
+                "result",
+                cntr_receipt.status,
+                ContractStatus::from_i32(cntr_receipt.status).map(contract_status_name),
+            );
+            obj = obj
+                .array("contractResult", vec![Value::String(hex::encode(&cntr_receipt.result))])
+                .address("contract_address", &cntr_receipt.created_address)
+                .array(
+                    "log",
+                    cntr_receipt
+                        .logs
+                        .iter()
+                        .map(|log| {
+                            JsonObject::new(false)
+                                .bytes("address", &log.address)
+                                .array("topics", log.topics.iter().map(|t| Value::String(hex::encode(t))).collect())
+                                .bytes("data", &log.data)
+                                .build()
+                        })
+                        .collect(),
+                );
+            if !receipt.success {
+                obj = obj
+                    .string("result", "FAILED")
+                    .bytes("resMessage", &cntr_receipt.message);
+            }
+        }
+        Some(ReceiptResult::CreatedAssetId(asset_id)) => {
+            obj = obj
+                .array("contractResult", vec![Value::String("".into())])
+                .string("assetIssueID", &asset_id.to_string());
+        }
+        Some(ReceiptResult::WithdrawalAmount(amount)) => {
+            obj = obj
+                .array("contractResult", vec![Value::String("".into())])
+                .int("withdraw_amount", amount);
+        }
+        Some(ReceiptResult::UnfrozenAmount(amount)) => {
+            obj = obj
+                .array("contractResult", vec![Value::String("".into())])
+                .int("unfreeze_amount", amount);
+        }
+        Some(ReceiptResult::Exchange(ref exchange)) => {
+            obj = obj
+                .array("contractResult", vec![Value::String("".into())])
+                .int("exchange_received_amount", exchange.received_amount)
+                .int("exchange_inject_another_amount", exchange.injected_amount)
+                .int("exchange_withdraw_another_amount", exchange.withdrawal_amount)
+                .int("exchange_id", exchange.created_exchange_id);
+        }
+        None => {
+            obj = obj.array("contractResult", vec![Value::String("".into())]);
+        }
+    }
+
+    obj.object("receipt", Some(resource_receipt.build())).build()
+}
@@ -0,0 +1,3 @@
+pub mod api;
+mod json;
+pub mod server;
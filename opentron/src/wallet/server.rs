@@ -0,0 +1,93 @@
+use futures::future::FutureExt;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use log::{info, warn};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use super::api::handle;
+use crate::context::AppContext;
+use crate::util::read_body;
+
+/// Parameters from query string, and JSON body of POST requests. Returns `None` if the body is too large.
+async fn request_params(req: Request<Body>) -> Result<Option<Value>, hyper::Error> {
+    let mut params = Map::new();
+    for pair in req.uri().query().unwrap_or_default().split('&') {
+        let mut kv = pair.splitn(2, '=');
+        if let (Some(key), Some(value)) = (kv.next(), kv.next()) {
+            params.insert(key.into(), Value::String(value.into()));
+        }
+    }
+    if req.method() == Method::POST {
+        let body = match read_body(req.into_body()).await? {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+        if let Ok(Value::Object(body_params)) = serde_json::from_slice(&body) {
+            params.extend(body_params);
+        }
+    }
+    Ok(Some(Value::Object(params)))
+}
+
+pub async fn wallet_api_server(ctx: Arc<AppContext>, mut shutdown_signal: broadcast::Receiver<()>) {
+    let config = &ctx.config.http_api;
+
+    if !config.enable {
+        warn!("http api server disabled");
+        return;
+    }
+
+    let addr = config.endpoint.parse().expect("malformed endpoint address");
+
+    let wallet_api_service = make_service_fn(move |_| {
+        let ctx = ctx.clone();
+
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let ctx = ctx.clone();
+
+                async move {
+                    let name = req.uri().path().strip_prefix("/wallet/").map(|name| name.to_owned());
+                    let reply = match name {
+                        Some(name) if req.method() == Method::GET || req.method() == Method::POST => {
+                            let params = match request_params(req).await? {
+                                Some(params) => params,
+                                None => {
+                                    let mut response = Response::new(Body::empty());
+                                    *response.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+                                    return Ok(response);
+                                }
+                            };
+                            handle(&ctx, &name, &params)
+                        }
+                        _ => None,
+                    };
+                    match reply {
+                        Some(reply) => {
+                            let mut response = Response::new(Body::from(reply.to_string()));
+                            response
+                                .headers_mut()
+                                .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+                            Ok::<_, hyper::Error>(response)
+                        }
+                        None => {
+                            let mut response = Response::new(Body::empty());
+                            *response.status_mut() = StatusCode::NOT_FOUND;
+                            Ok(response)
+                        }
+                    }
+                }
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr).serve(wallet_api_service);
+    info!("listening on http://{}", addr);
+
+    let _ = server.with_graceful_shutdown(shutdown_signal.recv().map(|_| ())).await;
+}